@@ -1,5 +1,5 @@
 echo "build and test"
-cargo build && cargo install --path .
+cargo build
 cd test
-moleculec --language cellscript --schema-file blockchain.mol > blockchain.cell
-git diff blockchain.cell
\ No newline at end of file
+../target/debug/moleculec-cellscript --schema-file blockchain.mol > blockchain.cell
+git diff blockchain.cell
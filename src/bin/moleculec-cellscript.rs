@@ -1,6 +1,11 @@
+use std::process;
+
 use molecule_codegen::IntermediateFormat;
 use moleculec_cellscript::build_commandline;
 
 fn main() {
-    build_commandline(IntermediateFormat::JSON).execute();
+    if let Err(err) = build_commandline(IntermediateFormat::JSON).execute() {
+        eprintln!("Error: {}", err);
+        process::exit(err.exit_code());
+    }
 }
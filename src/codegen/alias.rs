@@ -0,0 +1,179 @@
+//! Declarations with the same layout as an earlier one, which wrap its code instead of repeating
+//! it.
+
+use std::{collections::HashMap, rc::Rc};
+
+use molecule_codegen::ast::{self, HasName};
+
+use super::{doc::layout_doc, emitter::Emitter};
+use crate::{fingerprint::describe_shape, layout::DeclLayout, options::Options};
+
+/// The canonical declaration of each declaration which has the same shape as an earlier one, by
+/// name. The first declaration of a shape in the order of generation is the canonical one.
+pub(super) fn find_aliases(decls: &[Rc<ast::TopDecl>]) -> HashMap<String, String> {
+    let mut canonical = HashMap::new();
+    let mut aliases = HashMap::new();
+    for decl in decls {
+        let name = decl.name();
+        let canonical_name = canonical.entry(describe_shape(decl)).or_insert(name);
+        if *canonical_name != name {
+            aliases.insert(name.to_owned(), (*canonical_name).to_owned());
+        }
+    }
+    aliases
+}
+
+/// Writes a declaration as a wrapper of its canonical declaration.
+pub(super) fn write_alias(
+    e: &mut Emitter,
+    layout: &DeclLayout,
+    canonical: &str,
+    options: &Options,
+) {
+    let vars = [
+        ("struct_name", options.type_name(&layout.name)),
+        ("canonical_type", options.type_name(canonical)),
+    ];
+    let mut common = vars.to_vec();
+    common.push(("layout_doc", layout_doc(layout, options)));
+    e.template("alias", &common);
+    if options.components.builders {
+        e.template("alias_builder", &vars);
+    }
+    if options.components.verification {
+        e.template("alias_verification", &vars);
+    }
+    if options.as_builder() {
+        e.template("alias_as_builder", &vars);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use molecule_codegen::IntermediateFormat;
+
+    use super::*;
+    use crate::generate_to_string;
+
+    fn schema(declarations: &[&str]) -> ast::Ast {
+        let ir = format!(
+            r#"{{"namespace": "shapes", "imports": [], "declarations": [{}]}}"#,
+            declarations.join(", ")
+        );
+        IntermediateFormat::JSON.recover(ir.as_bytes()).unwrap()
+    }
+
+    fn point(name: &str, fields: [&str; 2]) -> String {
+        format!(
+            r#"{{"type": "struct", "name": "{}", "fields": [{{"name": "{}", "type": "byte"}},
+            {{"name": "{}", "type": "byte"}}], "imported_depth": 0}}"#,
+            name, fields[0], fields[1]
+        )
+    }
+
+    fn array(name: &str, item_count: usize) -> String {
+        format!(
+            r#"{{"type": "array", "name": "{}", "item": "byte", "item_count": {},
+            "imported_depth": 0}}"#,
+            name, item_count
+        )
+    }
+
+    fn generate(ast: &ast::Ast, alias_identical: bool) -> String {
+        let mut options = Options::default();
+        options.alias_identical = alias_identical;
+        generate_to_string(ast, &options).unwrap()
+    }
+
+    #[test]
+    fn identical_shapes_are_aliases_of_the_first() {
+        let ast = schema(&[
+            &array("Byte32", 32),
+            &array("Byte20", 20),
+            &array("Hash", 32),
+            &point("Point", ["x", "y"]),
+            &point("Size", ["x", "y"]),
+            &point("Range", ["start", "end"]),
+            &array("Uint256", 32),
+        ]);
+        let aliases = find_aliases(&ast.major_decls());
+        let mut aliases = aliases.into_iter().collect::<Vec<_>>();
+        aliases.sort();
+        let expected = [("Hash", "Byte32"), ("Size", "Point"), ("Uint256", "Byte32")];
+        assert_eq!(
+            aliases,
+            expected.map(|(alias, canonical)| (alias.to_owned(), canonical.to_owned()))
+        );
+    }
+
+    #[test]
+    fn an_alias_wraps_the_code_of_its_canonical_declaration() {
+        let ast = schema(&[&point("Point", ["x", "y"]), &point("Size", ["x", "y"])]);
+        let code = generate(&ast, true);
+        let (canonical, alias) = code.split_at(code.find("// Size is").unwrap());
+        // The canonical declaration is generated as without aliases.
+        assert!(generate(&ast, false).starts_with(canonical));
+        assert_eq!(
+            alias,
+            "// Size is a molecule struct, fixed size: 2 bytes.
+//   [0, 1)  x: Byte (1 byte)
+//   [1, 2)  y: Byte (1 byte)
+// It has the same layout as Point, whose code it shares.
+type Size struct {
+	Point
+}
+
+func SizeFromPoint(v Point) Size {
+	return Size{Point: v}
+}
+
+func (s *Size) AsPoint() Point {
+	return s.Point
+}
+
+func SizeFromSliceUnchecked(slice []byte) Size {
+	return Size{Point: PointFromSliceUnchecked(slice)}
+}
+
+func SizeDefault() Size {
+	return Size{Point: PointDefault()}
+}
+
+type SizeBuilder struct {
+	PointBuilder
+}
+
+func NewSizeBuilder() SizeBuilder {
+	return SizeBuilder{PointBuilder: NewPointBuilder()}
+}
+
+func (s *SizeBuilder) Build() Size {
+	return Size{Point: s.PointBuilder.Build()}
+}
+
+func SizeFromSlice(slice []byte, compatible bool) (ret Size, e error) {
+	inner, err := PointFromSlice(slice, compatible)
+	if err.NotNone() {
+		return ret, err
+	}
+	return Size{Point: inner}, errors.None()
+}
+
+func (s *Size) AsBuilder() SizeBuilder {
+	return SizeBuilder{PointBuilder: s.Point.AsBuilder()}
+}
+"
+        );
+    }
+
+    #[test]
+    fn shapes_are_only_shared_with_alias_identical() {
+        let ast = schema(&[&point("Point", ["x", "y"]), &point("Size", ["x", "y"])]);
+        let code = generate(&ast, false);
+        assert!(!code.contains("It has the same layout as"));
+        assert_eq!(
+            code.matches("func (s *Size) ").count(),
+            code.matches("func (s *Point) ").count()
+        );
+    }
+}
@@ -1,497 +1,255 @@
-use molecule_codegen::ast::{self, HasName};
+use molecule_codegen::ast;
 
-use case::CaseExt;
+use super::emitter::Emitter;
+use crate::layout::{DeclLayout, FieldLayout};
+use crate::options::Options;
 
 pub(in super::super) trait GenBuilder {
-    fn gen_builder(&self) -> String;
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options);
 }
 
 impl GenBuilder for ast::Option_ {
-    fn gen_builder(&self) -> String {
-        let struct_name = self.name().to_camel();
-        let inner_type = self.item().typ().name().to_camel();
-
-        let define = format!(
-            r#"
-type {struct_name}Builder struct {{
-	isNone bool
-	inner  {inner_type}
-}}
-func New{struct_name}Builder() {struct_name}Builder {{
-	return {struct_name}Builder{{isNone: true, inner: {inner_type}Default()}}
-}}
-func (s *{struct_name}Builder) Set(v {inner_type}) *{struct_name}Builder {{
-	s.isNone = false
-	s.inner = v
-	return s
-}}
-func (s *{struct_name}Builder) Build() {struct_name} {{
-	var ret {struct_name}
-	if s.isNone {{
-		ret = {struct_name}{{inner: []byte{{}}}}
-	}} else {{
-		ret = {struct_name}{{inner: s.inner.AsSlice()}}
-	}}
-	return ret
-}}
-            "#,
-            struct_name = struct_name,
-            inner_type = inner_type
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        e.template(
+            "option_builder",
+            &[
+                ("struct_name", options.type_name(&layout.name)),
+                ("inner_type", item_type(layout, options)),
+                (
+                    "inner_name",
+                    options
+                        .bare_type_name(&layout.item.as_ref().expect("options have an item").typ),
+                ),
+            ],
         );
-
-        define
     }
 }
 
 impl GenBuilder for ast::Union {
-    fn gen_builder(&self) -> String {
-        let struct_name = self.name().to_camel();
-        let define = format!(
-            r#"
-type {struct_name}Builder struct {{
-	inner  {struct_name}Union
-}}
-func New{struct_name}Builder() {struct_name}Builder {{
-    v := {struct_name}Default()
-    return {struct_name}Builder{{inner: v.ToUnion()}}
-}}
-func (s *{struct_name}Builder) Set(v {struct_name}Union) *{struct_name}Builder {{
-	s.inner = v
-	return s
-}}
-func (s *{struct_name}Builder) Build() {struct_name} {{
-	var b bytes.Buffer
-    b.Write(packNumber(s.inner.itemID))
-    b.Write(s.inner.AsSlice())
-
-    return {struct_name}{{inner: b.Bytes()}}
-}}
-          "#,
-            struct_name = struct_name
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        e.template(
+            "union_builder",
+            &[("struct_name", options.type_name(&layout.name))],
         );
-        define
     }
 }
 
 impl GenBuilder for ast::Array {
-    fn gen_builder(&self) -> String {
-        let struct_name = self.name().to_camel();
-        let inner_type = self.item().typ().name().to_camel();
-        let item_count = self.item_count();
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        let struct_name = options.type_name(&layout.name);
+        let inner_type = item_type(layout, options);
+        let item_count = layout.item_count.expect("arrays have an item count");
 
         let new_default = (0..item_count)
             .map(|_| format!("{}Default()", inner_type))
             .collect::<Vec<String>>()
-            .join(",");
-
-        let define = format!(
-            r#"
-type {struct_name}Builder struct {{
-	inner [{item_count}]{inner_type}
-}}
-
-func New{struct_name}Builder() {struct_name}Builder {{
-	return {struct_name}Builder{{inner: [{item_count}]{inner_type}{{{new_default}}}}}
-}}
-
-func (s *{struct_name}Builder) Build() {struct_name} {{
-	var b bytes.Buffer
-	len := len(s.inner)
-	for i := uint32(0); i < len; i++ {{
-		b.Write(s.inner[i].AsSlice())
-	}}
-	return {struct_name}{{inner: b.Bytes()}}
-}}
-        "#,
-            inner_type = inner_type,
-            item_count = item_count,
-            struct_name = struct_name,
-            new_default = new_default
+            .join(", ");
+
+        e.template(
+            "array_builder",
+            &[
+                ("struct_name", struct_name.clone()),
+                ("inner_type", inner_type.clone()),
+                ("item_count", item_count.to_string()),
+                ("default_items", new_default),
+            ],
         );
-
-        let entire_setter = format!(
-            r#"
-func (s *{struct_name}Builder) Set(v [{item_count}]{inner_type}) *{struct_name}Builder {{
-	s.inner = v
-	return s
-}}
-        "#,
-            struct_name = struct_name,
-            inner_type = inner_type,
-            item_count = item_count
-        );
-        let each_setter = (0..item_count)
-            .map(|index| {
-                format!(
-                    r#"
-func (s *{struct_name}Builder) Nth{index}(v {inner_type}) *{struct_name}Builder {{
-	s.inner[{index}] = v
-	return s
-}}
-                "#,
-                    struct_name = struct_name,
-                    inner_type = inner_type,
-                    index = index
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        vec![define, entire_setter, each_setter].join("\n")
+        for index in 0..item_count {
+            e.template(
+                "array_builder_nth",
+                &[
+                    ("struct_name", struct_name.clone()),
+                    ("inner_type", inner_type.clone()),
+                    ("index", index.to_string()),
+                ],
+            );
+        }
     }
 }
 
 impl GenBuilder for ast::Struct {
-    fn gen_builder(&self) -> String {
-        let struct_name = self.name().to_camel();
-
-        let define = def_builder_for_struct_or_table(&struct_name, self.fields());
-        let setter = impl_setters_for_struct_or_table(&struct_name, self.fields());
-        let default = impl_default_for_struct_or_table(&struct_name, self.fields());
-
-        let fields_encode = self
-            .fields()
-            .iter()
-            .map(|f| {
-                let field_name = &f.name();
-                format!("b.Write(s.{}.AsSlice())", field_name)
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        let build = format!(
-            r#"
-func (s *{struct_name}Builder) Build() {struct_name} {{
-    var b bytes.Buffer
-    {fields_encode}
-    return {struct_name}{{inner: b.Bytes()}}
-}}
-            "#,
-            struct_name = struct_name,
-            fields_encode = fields_encode
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        let struct_name = options.type_name(&layout.name);
+        let field_setters =
+            impl_setters_for_struct_or_table(e, &struct_name, &layout.fields, options);
+
+        e.template(
+            "struct_builder",
+            &[
+                ("struct_name", struct_name),
+                (
+                    "builder_fields",
+                    def_builder_fields(&layout.fields, options),
+                ),
+                ("field_writes", field_writes(&layout.fields, options)),
+                ("field_setters", field_setters),
+                ("default_fields", default_fields(&layout.fields, options)),
+            ],
         );
-
-        vec![define, build, setter, default].join("\n")
     }
 }
 
 impl GenBuilder for ast::FixVec {
-    fn gen_builder(&self) -> String {
-        let struct_name = self.name().to_camel();
-        let inner_name = self.item().typ().name().to_camel();
-
-        let define = def_builder_for_vector(&struct_name, &inner_name);
-        let setter = impl_setters_for_vector(&struct_name, &inner_name);
-        let default = impl_default_for_vector(&struct_name, &inner_name);
-
-        let build = format!(
-            r#"
-func (s *{struct_name}Builder) Build() {struct_name} {{
-    size := packNumber(Number(len(s.inner)))
-
-    var b bytes.Buffer
-
-    b.Write(size)
-    len := len(s.inner)
-    for i := uint32(0); i < len; i++ {{
-        b.Write(s.inner[i].AsSlice())
-    }}
-
-    sb := {struct_name}{{inner: b.Bytes()}}
-
-    return sb
-}}
-            "#,
-            struct_name = struct_name
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        e.template(
+            "fixvec_builder",
+            &[
+                ("struct_name", options.type_name(&layout.name)),
+                ("inner_type", item_type(layout, options)),
+            ],
         );
-
-        vec![define, build, setter, default].join("\n")
     }
 }
 
 impl GenBuilder for ast::DynVec {
-    fn gen_builder(&self) -> String {
-        let struct_name = self.name().to_camel();
-        let inner_name = self.item().typ().name().to_camel();
-
-        let define = def_builder_for_vector(&struct_name, &inner_name);
-        let setter = impl_setters_for_vector(&struct_name, &inner_name);
-        let default = impl_default_for_vector(&struct_name, &inner_name);
-        let build = format!(
-            r#"
-func (s *{struct_name}Builder) Build() {struct_name} {{
-    itemCount := len(s.inner)
-
-    var b bytes.Buffer
-
-    // Empty dyn vector, just return size's bytes
-    if itemCount == uint32(0) {{
-        b.Write(packNumber(Number(HeaderSizeUint)))
-        return {struct_name}{{inner: b.Bytes()}}
-    }}
-
-    // Calculate first offset then loop for rest items offsets
-    totalSize := HeaderSizeUint * (itemCount+uint32(1))
-    offsets := make([]uint32, 0, itemCount)
-    offsets = append(offsets, totalSize)
-    for i := uint32(1); i < itemCount; i++ {{
-        totalSize += uint32(len(s.inner[i-uint32(1)].AsSlice()))
-        offsets = append(offsets, offsets[i-uint32(1)]+uint32(len(s.inner[i-uint32(1)].AsSlice())))
-    }}
-    totalSize += uint32(len(s.inner[itemCount-uint32(1)].AsSlice()))
-
-    b.Write(packNumber(Number(totalSize)))
-
-    for i := uint32(0); i < itemCount; i++ {{
-        b.Write(packNumber(Number(offsets[i])))
-    }}
-
-    for i := uint32(0); i < itemCount; i++ {{
-        b.Write(s.inner[i].AsSlice())
-    }}
-
-    return {struct_name}{{inner: b.Bytes()}}
-}}
-          "#,
-            struct_name = struct_name
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        e.template(
+            "dynvec_builder",
+            &[
+                ("struct_name", options.type_name(&layout.name)),
+                ("inner_type", item_type(layout, options)),
+            ],
         );
-        vec![define, build, setter, default].join("\n")
     }
 }
 
 impl GenBuilder for ast::Table {
-    fn gen_builder(&self) -> String {
-        let field_count = self.fields().len();
-        let struct_name = self.name().to_camel();
-
-        let define = def_builder_for_struct_or_table(&struct_name, self.fields());
-        let setter = impl_setters_for_struct_or_table(&struct_name, self.fields());
-        let default = impl_default_for_struct_or_table(&struct_name, self.fields());
-
-        let build = if self.fields().is_empty() {
-            format!(
-                r#"
-func (s *{struct_name}Builder) Build() {struct_name} {{
-    var b bytes.Buffer
-    b.Write(packNumber(Number(HeaderSizeUint)))
-    return {struct_name}{{inner: b.Bytes()}}
-}}
-                "#,
-                struct_name = struct_name
-            )
-        } else {
-            let fields_offset = self
-                .fields()
-                .iter()
-                .map(|f| {
-                    let field_name = &f.name();
-                    format!("offsets = append(offsets, totalSize)\ntotalSize += uint32(len(s.{}.AsSlice()))", field_name)
-                })
-                .collect::<Vec<String>>()
-                .join("\n");
-            let fields_encode = self
-                .fields()
-                .iter()
-                .map(|f| {
-                    let field_name = &f.name();
-                    format!("b.Write(s.{}.AsSlice())", field_name)
-                })
-                .collect::<Vec<String>>()
-                .join("\n");
-
-            format!(
-                r#"
-func (s *{struct_name}Builder) Build() {struct_name} {{
-    var b bytes.Buffer
-
-    totalSize := HeaderSizeUint * uint32({field_count} + 1)
-    offsets := make([]uint32, 0, {field_count})
-
-    {fields_offset}
-
-    b.Write(packNumber(Number(totalSize)))
-
-    for i := uint32(0); i < len(offsets); i++ {{
-        b.Write(packNumber(Number(offsets[i])))
-    }}
-
-    {fields_encode}
-    return {struct_name}{{inner: b.Bytes()}}
-}}
-                "#,
-                struct_name = struct_name,
-                fields_offset = fields_offset,
-                fields_encode = fields_encode,
-                field_count = field_count
-            )
-        };
-        vec![define, build, setter, default].join("\n")
+    fn gen_builder(&self, e: &mut Emitter, layout: &DeclLayout, options: &Options) {
+        let struct_name = options.type_name(&layout.name);
+        if layout.fields.is_empty() {
+            e.template("empty_table_builder", &[("struct_name", struct_name)]);
+            return;
+        }
+
+        let field_offsets = layout
+            .fields
+            .iter()
+            .map(|f| {
+                let field_name = options.field_name(&f.name);
+                format!("offsets = append(offsets, totalSize)\ntotalSize += uint32(len(s.{}.AsSlice()))", field_name)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let field_setters =
+            impl_setters_for_struct_or_table(e, &struct_name, &layout.fields, options);
+
+        e.template(
+            "table_builder",
+            &[
+                ("struct_name", struct_name),
+                (
+                    "builder_fields",
+                    def_builder_fields(&layout.fields, options),
+                ),
+                ("field_count", layout.fields.len().to_string()),
+                ("field_offsets", field_offsets),
+                ("field_writes", field_writes(&layout.fields, options)),
+                ("field_setters", field_setters),
+                ("default_fields", default_fields(&layout.fields, options)),
+            ],
+        );
     }
 }
 
-fn def_builder_for_struct_or_table(struct_name: &str, inner: &[ast::FieldDecl]) -> String {
-    let fields = inner
+/// The fields of the builder, one `name Type` line for each field.
+fn def_builder_fields(inner: &[FieldLayout], options: &Options) -> String {
+    inner
         .iter()
         .map(|f| {
-            let field_name = &f.name();
-            let field_type = f.typ().name().to_camel();
-            format!("{} {}", field_name, field_type)
+            format!(
+                "{} {}",
+                options.field_name(&f.name),
+                options.type_name(&f.typ)
+            )
         })
-        .collect::<Vec<String>>()
-        .join("\n");
-
-    format!(
-        r#"
-type {struct_name}Builder struct {{
-    {fields}
-}}
-        "#,
-        struct_name = struct_name,
-        fields = fields
-    )
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn impl_default_for_struct_or_table(struct_name: &str, inner: &[ast::FieldDecl]) -> String {
-    let each_field = inner
+fn default_fields(inner: &[FieldLayout], options: &Options) -> String {
+    inner
         .iter()
         .map(|f| {
-            let field_name = &f.name();
-            let field_type = f.typ().name().to_camel();
             format!(
-                "{field_name}: {field_type}Default()",
-                field_name = field_name,
-                field_type = field_type
+                "{}: {}Default()",
+                options.field_name(&f.name),
+                options.type_name(&f.typ)
             )
         })
         .collect::<Vec<_>>()
-        .join(",");
-    format!(
-        r#"
-func New{struct_name}Builder() {struct_name}Builder {{
-	return {struct_name}Builder{{ {each_field} }}
-}}
-    "#,
-        struct_name = struct_name,
-        each_field = each_field
-    )
+        .join(", ")
 }
 
-fn impl_setters_for_struct_or_table(struct_name: &str, inner: &[ast::FieldDecl]) -> String {
+fn field_writes(inner: &[FieldLayout], options: &Options) -> String {
     inner
         .iter()
-        .map(|f| {
-            let func_name = f.name().to_camel();
-            let field_name = &f.name();
-            let field_type = f.typ().name().to_camel();
-            format!(
-                r#"
-func (s *{struct_name}Builder) {func_name}(v {field_type}) *{struct_name}Builder {{
-    s.{field_name} = v
-    return s
-}}
-            "#,
-                struct_name = struct_name,
-                field_name = field_name,
-                field_type = field_type,
-                func_name = func_name
-            )
-        })
+        .map(|f| format!("b.Write(s.{}.AsSlice())", options.field_name(&f.name)))
         .collect::<Vec<_>>()
         .join("\n")
 }
 
-pub(in super::super) fn impl_as_builder_for_struct_or_table(
+/// The setters of the builder, each one rendered from the `field_setter` template.
+fn impl_setters_for_struct_or_table(
+    e: &Emitter,
     struct_name: &str,
-    inner: &[ast::FieldDecl],
+    inner: &[FieldLayout],
+    options: &Options,
 ) -> String {
-    let each_field = inner
+    e.fragment(|e| {
+        for f in inner {
+            e.template(
+                "field_setter",
+                &[
+                    ("struct_name", struct_name.to_owned()),
+                    ("func_name", options.func_name(&f.name)),
+                    ("field_name", options.field_name(&f.name)),
+                    ("field_type", options.type_name(&f.typ)),
+                ],
+            );
+        }
+    })
+}
+
+pub(in super::super) fn impl_as_builder_for_struct_or_table(
+    e: &mut Emitter,
+    struct_name: &str,
+    inner: &[FieldLayout],
+    options: &Options,
+) {
+    let field_copies = inner
         .iter()
         .map(|f| {
-            let func_name = f.name().to_camel();
-            format!("ret.{filed_name} = s.{func_name}()", filed_name = f.name(), func_name = func_name)
+            format!(
+                "ret.{} = s.{}()",
+                options.field_name(&f.name),
+                options.func_name(&f.name)
+            )
         })
         .collect::<Vec<_>>()
         .join("\n");
-    format!(
-        r#"
-func (s *{struct_name}) AsBuilder() {struct_name}Builder {{
-    ret := New{struct_name}Builder()
-    {each_field}
-    return ret
-}}
-        "#,
-        struct_name = struct_name,
-        each_field = each_field
-    )
-}
-
-fn def_builder_for_vector(struct_name: &str, inner_name: &str) -> String {
-    format!(
-        r#"
-type {struct_name}Builder struct {{
-    inner []{inner_type}
-}}
-    "#,
-        struct_name = struct_name,
-        inner_type = inner_name
-    )
-}
-
-fn impl_setters_for_vector(struct_name: &str, inner_name: &str) -> String {
-    format!(
-        r#"
-func (s *{struct_name}Builder) Set(v []{inner_name}) *{struct_name}Builder {{
-    s.inner = v
-    return s
-}}
-func (s *{struct_name}Builder) Push(v {inner_name}) *{struct_name}Builder {{
-    s.inner = append(s.inner, v)
-    return s
-}}
-func (s *{struct_name}Builder) Extend(iter []{inner_name}) *{struct_name}Builder {{
-    for i:=uint32(0); i < len(iter); i++ {{
-        s.inner = append(s.inner, iter[i])
-    }}
-    return s
-}}
-func (s *{struct_name}Builder) Replace(index uint64, v {inner_name}) (ret {inner_name}) {{
-    if uint64(len(s.inner)) > index {{
-        a := s.inner[index]
-        s.inner[index] = v
-        return a
-    }}
-    return
-}}
-    "#,
-        struct_name = struct_name,
-        inner_name = inner_name
-    )
-}
-
-fn impl_default_for_vector(struct_name: &str, inner_name: &str) -> String {
-    format!(
-        r#"
-func New{struct_name}Builder() {struct_name}Builder {{
-	return {struct_name}Builder{{ inner: []{inner_name}{{}} }}
-}}
-        "#,
-        struct_name = struct_name,
-        inner_name = inner_name
-    )
-}
-
-pub(in super::super) fn impl_as_builder_for_vector(struct_name: &str) -> String {
-    format!(
-        r#"
-func (s *{struct_name}) AsBuilder() {struct_name}Builder {{
-    size := s.ItemCount()
-    t := New{struct_name}Builder()
-    for i:=uint64(0); i < size; i++ {{
-        t.Push(s.Get(i))
-    }}
-    return t
-}}
-        "#,
-        struct_name = struct_name
+    e.template(
+        "fields_as_builder",
+        &[
+            ("struct_name", struct_name.to_owned()),
+            ("field_copies", field_copies),
+        ],
+    );
+}
+
+pub(in super::super) fn impl_as_builder_for_vector(e: &mut Emitter, struct_name: &str) {
+    e.template(
+        "vector_as_builder",
+        &[("struct_name", struct_name.to_owned())],
+    );
+}
+
+/// The name of the item type of an option, an array or a vector.
+pub(in super::super) fn item_type(layout: &DeclLayout, options: &Options) -> String {
+    options.type_name(
+        &layout
+            .item
+            .as_ref()
+            .expect("the declaration has an item")
+            .typ,
     )
 }
@@ -0,0 +1,25 @@
+//! The dialects of CellScript which the generated code can target.
+//!
+//! The templates are written in the idioms of the only confirmed dialect, so there's nothing to
+//! translate yet. `--target` records which compiler the code is generated for, and rejects the
+//! releases whose idioms aren't known.
+
+/// A CellScript compiler release whose idioms the generated code follows.
+#[derive(Debug)]
+pub(crate) struct Dialect {
+    pub(crate) name: &'static str,
+}
+
+/// The dialect of each CellScript release whose idioms differ, keyed by the compiler version. Keep
+/// in sync with the table in the README.
+pub(crate) const DIALECTS: &[Dialect] = &[Dialect { name: "0.1" }];
+
+/// The dialect named `name`.
+pub(crate) fn find(name: &str) -> Option<&'static Dialect> {
+    DIALECTS.iter().find(|dialect| dialect.name == name)
+}
+
+/// The names of the dialects, for the messages.
+pub(crate) fn names() -> Vec<&'static str> {
+    DIALECTS.iter().map(|dialect| dialect.name).collect()
+}
@@ -0,0 +1,163 @@
+use crate::layout::{DeclLayout, FieldOffset, Kind, NUMBER_SIZE};
+use crate::options::Options;
+
+/// The comment above a generated type, which describes where its data lives.
+///
+/// It starts with the molecule kind and the size, followed by the byte ranges of the struct fields
+/// and the array items, or of the header slots of the dynamic types.
+pub(super) fn layout_doc(layout: &DeclLayout, options: &Options) -> String {
+    let name = options.type_name(&layout.name);
+    let item = layout
+        .item
+        .as_ref()
+        .map(|item| sized(&options.type_name(&item.typ), item.size))
+        .unwrap_or_default();
+    let header = range(0, NUMBER_SIZE);
+
+    let mut rows = Vec::new();
+    let mut notes = Vec::new();
+    let summary = match layout.kind {
+        Kind::Primitive => format!("{} is a molecule primitive type", name),
+        Kind::Option => {
+            notes.push(format!(
+                "It's empty for None, otherwise it holds the {}.",
+                item
+            ));
+            format!("{} is a molecule option of {}, dynamic size", name, item)
+        }
+        Kind::Union => {
+            rows.push((header, "item ID".to_owned()));
+            rows.push((format!("[{}, ..)", NUMBER_SIZE), "item data".to_owned()));
+            let items = layout
+                .union_items
+                .iter()
+                .map(|item| {
+                    format!(
+                        "{} {}",
+                        item.id,
+                        sized(&options.type_name(&item.typ), item.size)
+                    )
+                })
+                .collect::<Vec<_>>();
+            notes.push(format!("Items: {}.", items.join(", ")));
+            format!("{} is a molecule union, dynamic size", name)
+        }
+        Kind::Array => {
+            let count = layout.item_count.unwrap_or_default();
+            for i in 0..count {
+                if let Some(item_range) = layout.item_range(i) {
+                    rows.push((range(item_range.start, item_range.end), format!("Nth{}", i)));
+                }
+            }
+            format!(
+                "{} is a molecule array of {} {}, fixed size: {}",
+                name,
+                count,
+                item,
+                total_size(layout)
+            )
+        }
+        Kind::Struct => {
+            for field in &layout.fields {
+                if let FieldOffset::Fixed(field_range) = &field.offset {
+                    let typ = sized(&options.type_name(&field.typ), field.size);
+                    rows.push((
+                        range(field_range.start, field_range.end),
+                        format!("{}: {}", field.name, typ),
+                    ));
+                }
+            }
+            format!(
+                "{} is a molecule struct, fixed size: {}",
+                name,
+                total_size(layout)
+            )
+        }
+        Kind::FixVec => {
+            let item_size = layout.item_size().unwrap_or_default();
+            rows.push((header, "item count n".to_owned()));
+            rows.push((
+                format!("[{0}, {0}+{1}*n)", NUMBER_SIZE, item_size),
+                "the items".to_owned(),
+            ));
+            format!("{} is a molecule fixvec of {}, dynamic size", name, item)
+        }
+        Kind::DynVec => {
+            rows.push((header, "total size".to_owned()));
+            rows.push((
+                format!("[{0}, {0}+{0}*n)", NUMBER_SIZE),
+                "offsets of the n items".to_owned(),
+            ));
+            notes.push(
+                "Each item ends at the offset of the next one, the last one at the total size."
+                    .to_owned(),
+            );
+            format!("{} is a molecule dynvec of {}, dynamic size", name, item)
+        }
+        Kind::Table => {
+            rows.push((header, "total size".to_owned()));
+            for field in &layout.fields {
+                if let FieldOffset::Header(start) = field.offset {
+                    let typ = sized(&options.type_name(&field.typ), field.size);
+                    rows.push((
+                        range(start, start + NUMBER_SIZE),
+                        format!("offset of {}: {}", field.name, typ),
+                    ));
+                }
+            }
+            if layout.fields.is_empty() {
+                format!("{} is a molecule table without fields, dynamic size", name)
+            } else {
+                notes.push(
+                    "Each field ends at the offset of the next one, the last one at the total"
+                        .to_owned(),
+                );
+                notes.push("size or at the first extra field.".to_owned());
+                format!(
+                    "{} is a molecule table of {} fields, dynamic size",
+                    name,
+                    layout.fields.len()
+                )
+            }
+        }
+    };
+
+    let width = rows.iter().map(|(range, _)| range.len()).max().unwrap_or(0);
+    let mut lines = vec![format!("// {}.", summary)];
+    for (range, description) in rows {
+        lines.push(format!(
+            "//   {:width$}  {}",
+            range,
+            description,
+            width = width
+        ));
+    }
+    for note in notes {
+        lines.push(format!("// {}", note));
+    }
+    lines.join("\n")
+}
+
+fn range(start: usize, end: usize) -> String {
+    format!("[{}, {})", start, end)
+}
+
+fn bytes(size: usize) -> String {
+    if size == 1 {
+        "1 byte".to_owned()
+    } else {
+        format!("{} bytes", size)
+    }
+}
+
+fn total_size(layout: &DeclLayout) -> String {
+    bytes(layout.total_size.unwrap_or_default())
+}
+
+/// The type name, followed by its size if it's fixed.
+fn sized(typ: &str, size: Option<usize>) -> String {
+    match size {
+        Some(size) => format!("{} ({})", typ, bytes(size)),
+        None => typ.to_owned(),
+    }
+}
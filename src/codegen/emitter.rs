@@ -0,0 +1,157 @@
+//! An indentation-aware writer for the generated code.
+//!
+//! The emitters describe the structure of the code, blocks, functions, switches and comments, and
+//! the indentation follows from the nesting of the closures. The code of the declarations comes
+//! from the templates.
+
+use std::{collections::BTreeMap, mem};
+
+use super::template::Templates;
+use crate::stats::Counts;
+
+/// Collects the generated code.
+#[derive(Debug)]
+pub(crate) struct Emitter<'a> {
+    code: String,
+    indent: usize,
+    templates: &'a Templates,
+    /// The code of the templates written so far, by component.
+    counts: BTreeMap<&'static str, Counts>,
+}
+
+impl<'a> Emitter<'a> {
+    pub(crate) fn new(templates: &'a Templates) -> Self {
+        Self {
+            code: String::new(),
+            indent: 0,
+            templates,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// The counts of the code written by the templates since the last call, by component.
+    ///
+    /// The fragments are counted as part of the templates they're substituted into.
+    pub(crate) fn take_counts(&mut self) -> BTreeMap<&'static str, Counts> {
+        mem::take(&mut self.counts)
+    }
+
+    /// The code written so far.
+    pub(crate) fn into_code(self) -> String {
+        self.code
+    }
+
+    /// Writes a line at the current indentation.
+    pub(crate) fn line<S: AsRef<str>>(&mut self, line: S) -> &mut Self {
+        let line = line.as_ref();
+        if !line.is_empty() {
+            self.code.push_str(&"\t".repeat(self.indent));
+            self.code.push_str(line);
+        }
+        self.code.push('\n');
+        self
+    }
+
+    /// Writes several lines at the current indentation.
+    pub(crate) fn lines<I, S>(&mut self, lines: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for line in lines {
+            self.line(line);
+        }
+        self
+    }
+
+    /// Writes the template `name`, separated from the preceding code by a blank line.
+    pub(crate) fn template(&mut self, name: &str, variables: &[(&str, String)]) -> &mut Self {
+        let code = self.templates.render(name, variables);
+        *self.counts.entry(Templates::component(name)).or_default() += Counts::of(&code);
+        self.blank().lines(code.lines())
+    }
+
+    /// Emits a fragment of code, which becomes the value of a template variable.
+    ///
+    /// The fragment starts one level deep, so it may hold the cases of a `switch`; the formatter
+    /// lays out the code after the variables are substituted.
+    pub(crate) fn fragment<F: FnOnce(&mut Self)>(&self, body: F) -> String {
+        let mut e = Self::new(self.templates);
+        e.indent = 1;
+        body(&mut e);
+        e.code.trim_end().to_owned()
+    }
+
+    /// Writes a blank line, unless it would follow another blank line or open the output.
+    pub(crate) fn blank(&mut self) -> &mut Self {
+        if !self.code.is_empty() && !self.code.ends_with("\n\n") {
+            self.code.push('\n');
+        }
+        self
+    }
+
+    /// Writes a line comment, one `//` for each line of `text`.
+    pub(crate) fn comment<S: AsRef<str>>(&mut self, text: S) -> &mut Self {
+        for line in text.as_ref().lines() {
+            if line.is_empty() {
+                self.line("//");
+            } else {
+                self.line(format!("// {}", line));
+            }
+        }
+        self
+    }
+
+    /// Writes `head {`, the body one level deeper, and the closing brace.
+    pub(crate) fn block<S, F>(&mut self, head: S, body: F) -> &mut Self
+    where
+        S: AsRef<str>,
+        F: FnOnce(&mut Self),
+    {
+        self.delimited(&format!("{} {{", head.as_ref()), "}", body)
+    }
+
+    /// Writes `head (`, the body one level deeper, and the closing parenthesis.
+    pub(crate) fn parens<S, F>(&mut self, head: S, body: F) -> &mut Self
+    where
+        S: AsRef<str>,
+        F: FnOnce(&mut Self),
+    {
+        self.delimited(&format!("{} (", head.as_ref()), ")", body)
+    }
+
+    /// Writes a function, separated from the preceding code by a blank line.
+    pub(crate) fn func<S, F>(&mut self, signature: S, body: F) -> &mut Self
+    where
+        S: AsRef<str>,
+        F: FnOnce(&mut Self),
+    {
+        self.blank()
+            .block(format!("func {}", signature.as_ref()), body)
+    }
+
+    /// Writes a case of the enclosing `switch`. The label is aligned with the `switch`.
+    pub(crate) fn case<S, F>(&mut self, expr: S, body: F) -> &mut Self
+    where
+        S: AsRef<str>,
+        F: FnOnce(&mut Self),
+    {
+        self.label(&format!("case {}:", expr.as_ref()), body)
+    }
+
+    fn label<F: FnOnce(&mut Self)>(&mut self, label: &str, body: F) -> &mut Self {
+        self.indent -= 1;
+        self.line(label);
+        self.indent += 1;
+        body(self);
+        self
+    }
+
+    fn delimited<F: FnOnce(&mut Self)>(&mut self, open: &str, close: &str, body: F) -> &mut Self {
+        self.line(open);
+        self.indent += 1;
+        body(self);
+        self.indent -= 1;
+        self.line(close)
+    }
+}
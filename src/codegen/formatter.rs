@@ -0,0 +1,351 @@
+//! A layout pass over the generated CellScript code.
+//!
+//! The emitters only care about the tokens they produce, this pass takes care of the layout: tabs
+//! for indentation, case clauses aligned with their `switch`, canonical spacing around commas and
+//! inside composite literals, aligned struct fields, and at most one blank line in a row.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Plain,
+    Switch,
+    Struct,
+}
+
+#[derive(Debug)]
+struct Line {
+    indent: usize,
+    text: String,
+    is_field: bool,
+}
+
+/// Formats the generated code.
+pub(crate) fn format(code: &str) -> String {
+    let lines = indent_lines(code);
+    let mut lines = normalize_blank_lines(lines);
+    align_fields(&mut lines);
+
+    let mut output = String::with_capacity(code.len());
+    for line in lines.iter() {
+        if let Some(line) = line {
+            output.push_str(&"\t".repeat(line.indent));
+            output.push_str(&line.text);
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Computes the indentation of each line from the brackets which are still open. Blank lines are
+/// kept as `None`.
+fn indent_lines(code: &str) -> Vec<Option<Line>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut lines = Vec::new();
+    for raw in code.lines() {
+        let text = normalize_spacing(raw.trim());
+        if text.is_empty() {
+            lines.push(None);
+            continue;
+        }
+
+        let leading_closers = text.chars().take_while(|c| is_closer(*c)).count();
+        let depth = blocks.len().saturating_sub(leading_closers);
+        let enclosing = if depth > 0 {
+            Some(blocks[depth - 1])
+        } else {
+            None
+        };
+        let indent = if enclosing == Some(Block::Switch) && is_case_label(&text) {
+            depth - 1
+        } else {
+            depth
+        };
+        let is_field = enclosing == Some(Block::Struct) && leading_closers == 0 && is_field(&text);
+
+        let brackets = code_chars(&text)
+            .filter(|c| is_opener(*c) || is_closer(*c))
+            .collect::<Vec<_>>();
+        for (i, bracket) in brackets.iter().enumerate() {
+            if is_closer(*bracket) {
+                blocks.pop();
+            } else if *bracket == '{' && i == brackets.len() - 1 && text.ends_with('{') {
+                blocks.push(block_kind(&text));
+            } else {
+                blocks.push(Block::Plain);
+            }
+        }
+
+        lines.push(Some(Line {
+            indent,
+            text,
+            is_field,
+        }));
+    }
+    lines
+}
+
+/// Collapses blank lines, drops them at the edges of blocks, and separates top-level blocks by
+/// exactly one blank line.
+fn normalize_blank_lines(lines: Vec<Option<Line>>) -> Vec<Option<Line>> {
+    let mut output: Vec<Option<Line>> = Vec::with_capacity(lines.len());
+    let mut pending_blank = false;
+    for line in lines {
+        let line = match line {
+            Some(line) => line,
+            None => {
+                pending_blank = true;
+                continue;
+            }
+        };
+        if let Some(Some(previous)) = output.last() {
+            let after_opener = previous.text.ends_with('{') || previous.text.ends_with('(');
+            let before_closer = line.text.starts_with(is_closer) || is_case_label(&line.text);
+            let after_top_level_block = previous.indent == 0
+                && line.indent == 0
+                && !previous.text.starts_with("//")
+                && (previous.text.ends_with('}')
+                    || previous.text.ends_with(')')
+                    || previous.text.starts_with("package ")
+                    || line.text.starts_with("func "));
+            if (pending_blank && !after_opener && !before_closer) || after_top_level_block {
+                output.push(None);
+            }
+        }
+        pending_blank = false;
+        output.push(Some(line));
+    }
+    output
+}
+
+/// Aligns the types of consecutive struct fields, as gofmt does.
+fn align_fields(lines: &mut [Option<Line>]) {
+    let mut start = 0;
+    while start < lines.len() {
+        let is_field =
+            |line: &Option<Line>| line.as_ref().map(|line| line.is_field).unwrap_or(false);
+        if !is_field(&lines[start]) {
+            start += 1;
+            continue;
+        }
+        let end = start
+            + lines[start..]
+                .iter()
+                .take_while(|line| is_field(line))
+                .count();
+        let width = lines[start..end]
+            .iter()
+            .flatten()
+            .map(|line| line.text.find(' ').unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        for line in lines[start..end].iter_mut().flatten() {
+            let (name, typ) = line.text.split_at(line.text.find(' ').unwrap_or(0));
+            line.text = format!("{:width$} {}", name, typ.trim_start(), width = width);
+        }
+        start = end;
+    }
+}
+
+/// Canonical spacing within a line: single spaces between tokens, a space after commas and
+/// around `:=`, and no spaces just inside brackets. Strings and comments are left as they are.
+fn normalize_spacing(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut output = String::with_capacity(text.len());
+    let mut quote = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if let Some(q) = quote {
+            output.push(c);
+            if c == '\\' && q != '`' {
+                if let Some(escaped) = next {
+                    output.push(escaped);
+                    i += 1;
+                }
+            } else if c == q {
+                quote = None;
+            }
+            i += 1;
+            continue;
+        }
+        match c {
+            '"' | '`' | '\'' => {
+                quote = Some(c);
+                output.push(c);
+            }
+            '/' if next == Some('/') => {
+                if !output.is_empty() && !output.ends_with(' ') {
+                    output.push(' ');
+                }
+                output.extend(&chars[i..]);
+                break;
+            }
+            ' ' | '\t' => {
+                while chars
+                    .get(i + 1)
+                    .map(|c| *c == ' ' || *c == '\t')
+                    .unwrap_or(false)
+                {
+                    i += 1;
+                }
+                let next = chars.get(i + 1).copied();
+                let after_opener = output.ends_with(is_opener);
+                let before_closer = next.map(|c| is_closer(c) || c == ',').unwrap_or(true);
+                if !after_opener && !before_closer {
+                    output.push(' ');
+                }
+            }
+            ',' => {
+                output.push(',');
+                if next.map(|c| c != ' ').unwrap_or(false) {
+                    output.push(' ');
+                }
+            }
+            ':' if next == Some('=') => {
+                if !output.ends_with(' ') {
+                    output.push(' ');
+                }
+                output.push_str(":=");
+                i += 1;
+                if chars.get(i + 1).map(|c| *c != ' ').unwrap_or(false) {
+                    output.push(' ');
+                }
+            }
+            _ => output.push(c),
+        }
+        i += 1;
+    }
+    output.trim_end().to_owned()
+}
+
+/// The characters of a line which are code, skipping strings and comments.
+fn code_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = None;
+    let mut in_comment = false;
+    text.chars().filter(move |c| {
+        let c = *c;
+        if in_comment {
+            return false;
+        }
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' && q != '`' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            return false;
+        }
+        match c {
+            '"' | '`' | '\'' => {
+                quote = Some(c);
+                false
+            }
+            '/' if previous == Some('/') => {
+                in_comment = true;
+                false
+            }
+            _ => {
+                previous = Some(c);
+                true
+            }
+        }
+    })
+}
+
+fn block_kind(text: &str) -> Block {
+    let first_word = text
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default();
+    match first_word {
+        "switch" | "select" => Block::Switch,
+        "type" if text.ends_with(" struct {") => Block::Struct,
+        _ => Block::Plain,
+    }
+}
+
+fn is_case_label(text: &str) -> bool {
+    (text.starts_with("case ") || text.starts_with("default:")) && text.ends_with(':')
+}
+
+/// A struct field declaration: a name followed by a type, without tags or comments.
+fn is_field(text: &str) -> bool {
+    let mut parts = text.splitn(2, ' ');
+    let name = parts.next().unwrap_or_default();
+    parts.next().is_some()
+        && name.chars().all(|c| c == '_' || c.is_alphanumeric())
+        && !text.contains("//")
+        && !text.contains('"')
+}
+
+fn is_opener(c: char) -> bool {
+    c == '{' || c == '(' || c == '['
+}
+
+fn is_closer(c: char) -> bool {
+    c == '}' || c == ')' || c == ']'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_code_is_left_as_it_is() {
+        let code = include_str!("../../test/blockchain.cell");
+        assert_eq!(format(code), code);
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let code =
+            "package x\nfunc f( a int ,b int ) {\n  switch a {\n    case 1 :\nreturn\n\n\n  }\n}\n";
+        let formatted = format(code);
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn case_labels_are_aligned_with_the_switch() {
+        assert_eq!(
+            format("func f() {\nswitch x {\ncase 1:\nreturn a\ndefault:\nreturn b\n}\n}\n"),
+            "func f() {\n\tswitch x {\n\tcase 1:\n\t\treturn a\n\tdefault:\n\t\treturn b\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn struct_fields_are_aligned() {
+        assert_eq!(
+            format("type T struct {\nlock Script\ncode_hash Byte32\n\ntype_ ScriptOpt\n}\n"),
+            "type T struct {\n\tlock      Script\n\tcode_hash Byte32\n\n\ttype_ ScriptOpt\n}\n"
+        );
+    }
+
+    #[test]
+    fn brackets_in_strings_and_comments_are_not_code() {
+        let code = "func f() {\nx := \"{(\"\ny := `}`\nz := '{' // {\n}\nvar a = 1\n";
+        assert_eq!(
+            format(code),
+            "func f() {\n\tx := \"{(\"\n\ty := `}`\n\tz := '{' // {\n}\n\nvar a = 1\n"
+        );
+    }
+
+    #[test]
+    fn spacing_is_normalized_outside_strings_and_comments() {
+        assert_eq!(
+            format("x:=f( a ,b )  // a ,b\ns := \"a ,b  c\"\n"),
+            "x := f(a, b) // a ,b\ns := \"a ,b  c\"\n"
+        );
+    }
+
+    #[test]
+    fn blank_lines_are_collapsed() {
+        assert_eq!(
+            format("func f() {\n\n\na := 1\n\n\n\nb := 2\n\n}\nfunc g() {\n}\n"),
+            "func f() {\n\ta := 1\n\n\tb := 2\n}\n\nfunc g() {\n}\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! The imports of the generated code, found from the packages which the code refers to.
+
+/// The runtime packages which the generated code may use, by the names they're referred to with.
+const PACKAGES: &[(&str, &str)] = &[
+    ("binary", "encoding/binary"),
+    ("bytes", "bytes"),
+    ("errors", "errors"),
+    ("strconv", "strconv"),
+    ("strings", "strings"),
+];
+
+/// The import paths of the packages which `code` refers to, sorted.
+///
+/// The candidates are the runtime packages and the packages of the imported schemas, given as
+/// their names and import paths. The code is scanned as it's emitted, so whatever the
+/// templates and the extensions emit is covered. Strings and comments don't count.
+pub(super) fn used_imports<'a>(code: &str, schema_packages: &[(&str, &'a str)]) -> Vec<&'a str> {
+    let packages = PACKAGES.iter().chain(schema_packages).collect::<Vec<_>>();
+    let mut used = vec![false; packages.len()];
+    for line in code.lines() {
+        for (_, text) in split_code(line).into_iter().filter(|(is_code, _)| *is_code) {
+            for (i, (name, _)) in packages.iter().enumerate() {
+                used[i] = used[i] || refers_to(text, name);
+            }
+        }
+    }
+    let mut paths = packages
+        .into_iter()
+        .zip(used)
+        .filter(|(_, used)| *used)
+        .map(|((_, path), _)| *path)
+        .collect::<Vec<_>>();
+    paths.sort_unstable();
+    paths.dedup();
+    paths
+}
+
+/// Whether `code` holds a selector `<name>.`, which isn't itself the member of something.
+fn refers_to(code: &str, name: &str) -> bool {
+    code.match_indices(name).any(|(i, _)| {
+        code[i + name.len()..].starts_with('.')
+            && !code[..i].ends_with(|c: char| c == '.' || is_identifier_char(c))
+    })
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Splits a line into code and the strings and comments in it, `true` marks the code.
+fn split_code(line: &str) -> Vec<(bool, &str)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if line[i..].starts_with("//") => {
+                parts.push((true, &line[start..i]));
+                parts.push((false, &line[i..]));
+                return parts;
+            }
+            '"' | '`' | '\'' => {
+                parts.push((true, &line[start..i]));
+                let mut end = line.len();
+                let mut escaped = false;
+                for (j, d) in chars.by_ref() {
+                    if escaped {
+                        escaped = false;
+                    } else if d == '\\' && c != '`' {
+                        escaped = true;
+                    } else if d == c {
+                        end = j + 1;
+                        break;
+                    }
+                }
+                parts.push((false, &line[i..end]));
+                start = end;
+            }
+            _ => {}
+        }
+    }
+    parts.push((true, &line[start..]));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_and_comments_are_split_from_the_code() {
+        assert_eq!(
+            split_code(r#"a := "b.c\"d" + x // e.f"#),
+            [
+                (true, "a := "),
+                (false, r#""b.c\"d""#),
+                (true, " + x "),
+                (false, "// e.f"),
+            ]
+        );
+        assert_eq!(
+            split_code("`a\\` + b"),
+            [(true, ""), (false, "`a\\`"), (true, " + b")]
+        );
+    }
+
+    #[test]
+    fn only_the_packages_which_the_code_refers_to_are_imported() {
+        let code = "n := binary.LittleEndian.Uint32(s.inner)\n\
+                    return errors.New(\"strconv.Itoa\") // bytes.Equal\n\
+                    x := s.strings.Len() + basic.Hash{}\n";
+        assert_eq!(
+            used_imports(
+                code,
+                &[("basic", "example.com/basic"), ("ext", "example.com/ext")]
+            ),
+            ["encoding/binary", "errors", "example.com/basic"]
+        );
+    }
+}
@@ -1,89 +1,288 @@
-use molecule_codegen::{ast, VERSION};
+use molecule_codegen::{
+    ast::{self, HasName},
+    VERSION,
+};
 pub const PLUGIN_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+use crate::{
+    fingerprint,
+    ident::Rename,
+    layout::Layout,
+    options::Options,
+    stats::{Counts, DeclStats, Stats},
+    Error,
+};
+
 pub(crate) struct Generator;
 
-use std::io;
+use case::CaseExt;
+use std::{collections::HashMap, io, rc::Rc};
 
+mod alias;
+mod emitter;
+use emitter::Emitter;
 mod generator;
 use generator::Generator as _;
 mod builder;
+pub(crate) mod dialect;
+mod doc;
+mod formatter;
+mod imports;
+mod runtime;
+mod select;
+mod template;
+use template::Templates;
 mod union;
 
+/// The name of the file which holds the prelude in the split output.
+pub(crate) const PRELUDE_FILE_NAME: &str = "molecule_prelude.cell";
+/// Every generated file carries this marker, so stale files can be recognized.
+pub(crate) const GENERATED_MARKER: &str = "// Generated by Moleculec-CellScript";
+
 impl Generator {
-    pub fn generate<W: io::Write>(writer: &mut W, ast: &ast::Ast) -> io::Result<()> {
-        writeln!(writer, "// Generated by Molecule {}", VERSION)?;
-        writeln!(writer, "// Generated by Moleculec-CellScript {}", PLUGIN_VERSION)?;
-
-        let import = format!(
-            r#"
-package {}
-import (
-    "bytes"
-    "encoding/binary"
-    "errors"
-    "strconv"
-    "strings"
-       )"#,
-            ast.namespace()
+    pub fn generate<W: io::Write>(
+        writer: &mut W,
+        ast: &ast::Ast,
+        options: &Options,
+    ) -> Result<Stats, Error> {
+        let layout = Layout::new(ast);
+        let options = &options.resolve(ast, &layout)?;
+        let decls = select::select_decls(ast, &layout, options)?;
+        Self::check_renames(&decls, &layout, options)?;
+        let templates =
+            Templates::load(options.template_dir.as_deref(), runtime::variables(options))?;
+        let mut e = Emitter::new(&templates);
+        let aliases = Self::aliases(&decls, options);
+        let mut stats = Stats::default();
+        if options.runtime.is_none() {
+            runtime::write_runtime(&mut e, false);
+        }
+        for decl in &decls {
+            Self::write_decl(&mut e, decl, &layout, &aliases, options, &mut stats)?;
+        }
+        stats.find_biggest();
+        writer
+            .write_all(Self::format(&templates, e, Some(ast), options).as_bytes())
+            .map_err(Error::Generate)?;
+        Ok(stats)
+    }
+
+    /// Generates the runtime as a package of its own, which the schema packages import when
+    /// `runtime` is set.
+    pub fn generate_runtime<W: io::Write>(writer: &mut W, options: &Options) -> Result<(), Error> {
+        options.validate_runtime()?;
+        let templates = Templates::load(None, Vec::new())?;
+        let mut e = Emitter::new(&templates);
+        runtime::write_runtime(&mut e, true);
+        writer
+            .write_all(Self::format(&templates, e, None, options).as_bytes())
+            .map_err(Error::Generate)
+    }
+
+    /// Generates one file per declaration, plus a file for the prelude.
+    ///
+    /// Returns the file names and their contents, the declarations are counted into `stats`.
+    pub fn generate_split(
+        ast: &ast::Ast,
+        options: &Options,
+        stats: &mut Stats,
+    ) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        let layout = Layout::new(ast);
+        let options = &options.resolve(ast, &layout)?;
+        let decls = select::select_decls(ast, &layout, options)?;
+        Self::check_renames(&decls, &layout, options)?;
+        let templates =
+            Templates::load(options.template_dir.as_deref(), runtime::variables(options))?;
+        let aliases = Self::aliases(&decls, options);
+
+        let mut files = Vec::with_capacity(decls.len() + 1);
+        let mut owners = Vec::with_capacity(decls.len() + 1);
+        if options.runtime.is_none() {
+            let mut e = Emitter::new(&templates);
+            runtime::write_runtime(&mut e, false);
+            let code = Self::format(&templates, e, Some(ast), options);
+            files.push((PRELUDE_FILE_NAME.to_owned(), code.into_bytes()));
+            owners.push("the prelude".to_owned());
+        }
+        for decl in &decls {
+            let file_name = format!("{}.cell", options.type_name(decl.name()).to_snake());
+            let mut e = Emitter::new(&templates);
+            Self::write_decl(&mut e, decl, &layout, &aliases, options, stats)?;
+            files.push((
+                file_name,
+                Self::format(&templates, e, Some(ast), options).into_bytes(),
+            ));
+            owners.push(format!("\"{}\"", decl.name()));
+        }
+
+        let mut seen = HashMap::new();
+        for ((file_name, _), owner) in files.iter().zip(&owners) {
+            if let Some(previous) = seen.insert(file_name, owner) {
+                return Err(Error::Config(format!(
+                    "{} and {} would both be written into {}",
+                    previous, owner, file_name
+                )));
+            }
+        }
+        stats.find_biggest();
+        Ok(files)
+    }
+
+    /// The names of the generated declarations which are escaped because they're reserved in
+    /// CellScript.
+    pub fn renames(ast: &ast::Ast, options: &Options) -> Result<Vec<Rename>, Error> {
+        let layout = Layout::new(ast);
+        let options = &options.resolve(ast, &layout)?;
+        let decls = select::select_decls(ast, &layout, options)?;
+        Self::check_renames(&decls, &layout, options)
+    }
+
+    /// Finds the escaped names of the declarations, and checks that they don't collide with the
+    /// names of other declarations.
+    fn check_renames(
+        decls: &[Rc<ast::TopDecl>],
+        layout: &Layout,
+        options: &Options,
+    ) -> Result<Vec<Rename>, Error> {
+        let mut renames = Vec::new();
+        for decl in decls {
+            let layout = layout
+                .get(decl.name())
+                .expect("every declaration has a layout");
+            renames.extend(options.renames(layout)?);
+        }
+        for rename in renames.iter().filter(|rename| rename.field.is_none()) {
+            if decls.iter().any(|decl| {
+                decl.name() != rename.decl && options.type_name(decl.name()) == rename.escaped
+            }) {
+                return Err(Error::Config(format!(
+                    "{}, which is the name of another declaration",
+                    rename
+                )));
+            }
+        }
+        Ok(renames)
+    }
+
+    /// Puts the file head with the imports the code uses in front of the emitted code, and lays it
+    /// out, so it doesn't need to be passed through gofmt.
+    ///
+    /// The schema is missing for the runtime package.
+    fn format(
+        templates: &Templates,
+        e: Emitter,
+        ast: Option<&ast::Ast>,
+        options: &Options,
+    ) -> String {
+        let code = e.into_code();
+        let mut packages = options.schema_imports();
+        if let (Some(_), Some(path)) = (ast, options.runtime.as_deref()) {
+            packages.push((options.runtime_package(), path));
+        }
+        let imports = imports::used_imports(&code, &packages);
+        let mut head = Emitter::new(templates);
+        Self::write_file_head(&mut head, ast, options, &imports);
+        head.blank();
+        formatter::format(&(head.into_code() + &code))
+    }
+
+    /// Writes the header comments, the package clause and the imports.
+    fn write_file_head(
+        e: &mut Emitter,
+        ast: Option<&ast::Ast>,
+        options: &Options,
+        imports: &[&str],
+    ) {
+        if options.header.versions {
+            e.line(format!("// Generated by Molecule {}", VERSION));
+            e.line(format!("{} {}", GENERATED_MARKER, PLUGIN_VERSION));
+        } else {
+            e.line(GENERATED_MARKER);
+        }
+        if let Some(ast) = ast {
+            if options.header.schema {
+                e.line(format!("// Schema: {}", options.schema_name(ast)));
+            }
+            if options.header.fingerprint {
+                e.line(format!(
+                    "// Schema fingerprint: {}",
+                    fingerprint::schema_fingerprint(ast)
+                ));
+            }
+        }
+        e.blank();
+        let package = ast.map_or_else(
+            || options.runtime_package(),
+            |ast| options.package_name(ast),
         );
-        writeln!(writer, "{}", import)?;
-
-        let major_imports = ast.imports();
-        if major_imports.is_empty() {
-            let primitive = String::from(
-                r#"
-type Number uint32
-const HeaderSizeUint = uint32(4)
-// Byte is the primitive type
-type Byte byte
-func NewByte(b byte) Byte {
-    return b
-}
-func ByteDefault() Byte {
-    return Byte(0)
-}
-func ByteFromSliceUnchecked(slice []byte) Byte {
-    return slice[0]
-}
-func (b Byte) AsSlice() []byte {
-    return []byte{b}
-}
-func ByteFromSlice(slice []byte, _compatible bool) (b Byte, e error) {
-    if len(slice) != uint32(1) {
-        return b, errors.New("TotalSizeNotMatch")
+        e.line(format!("package {}", package));
+        if !imports.is_empty() {
+            e.blank();
+            e.parens("import", |e| {
+                e.lines(imports.iter().map(|path| format!("\"{}\"", path)));
+            });
+        }
     }
-    b = slice[0]
-    return b, errors.None()
-}
-func unpackNumber(b []byte) Number {
-    bytesBuffer := bytes.NewBuffer(b)
-    x := binary.ReadUint32(bytesBuffer, binary.LittleEndian)
-    return Number(x)
-}
-func packNumber(num Number) []byte {
-    b := make([]byte, 4)
-    binary.LittleEndian.PutUint32(b, uint32(num))
-    return b
-}
-                    "#,
-            );
 
-            writeln!(writer, "{}", primitive)?;
+    /// The declarations which wrap the code of another one with `alias_identical`, see
+    /// [`alias::find_aliases`].
+    fn aliases(decls: &[Rc<ast::TopDecl>], options: &Options) -> HashMap<String, String> {
+        if options.alias_identical {
+            alias::find_aliases(decls)
+        } else {
+            HashMap::new()
         }
+    }
 
-        for decl in ast.major_decls() {
-            match decl.as_ref() {
-                ast::TopDecl::Option_(ref i) => i.generate(writer)?,
-                ast::TopDecl::Union(ref i) => i.generate(writer)?,
-                ast::TopDecl::Array(ref i) => i.generate(writer)?,
-                ast::TopDecl::Struct(ref i) => i.generate(writer)?,
-                ast::TopDecl::FixVec(ref i) => i.generate(writer)?,
-                ast::TopDecl::DynVec(ref i) => i.generate(writer)?,
-                ast::TopDecl::Table(ref i) => i.generate(writer)?,
+    /// Writes the code of a declaration, followed by the code of the extensions, and counts it.
+    fn write_decl(
+        e: &mut Emitter,
+        decl: &ast::TopDecl,
+        layout: &Layout,
+        aliases: &HashMap<String, String>,
+        options: &Options,
+        stats: &mut Stats,
+    ) -> Result<(), Error> {
+        let layout = layout
+            .get(decl.name())
+            .expect("every declaration has a layout");
+        if let Some(canonical) = aliases.get(decl.name()) {
+            alias::write_alias(e, layout, canonical, options);
+        } else {
+            match decl {
+                ast::TopDecl::Option_(ref i) => i.generate(e, layout, options),
+                ast::TopDecl::Union(ref i) => i.generate(e, layout, options),
+                ast::TopDecl::Array(ref i) => i.generate(e, layout, options),
+                ast::TopDecl::Struct(ref i) => i.generate(e, layout, options),
+                ast::TopDecl::FixVec(ref i) => i.generate(e, layout, options),
+                ast::TopDecl::DynVec(ref i) => i.generate(e, layout, options),
+                ast::TopDecl::Table(ref i) => i.generate(e, layout, options),
                 ast::TopDecl::Primitive(_) => unreachable!(),
-            };
+            }
+        }
+        let mut components = e.take_counts();
+        for extension in options.extensions() {
+            let mut code = Vec::new();
+            extension
+                .after_decl(decl, layout, &mut code)
+                .map_err(Error::Generate)?;
+            let code = String::from_utf8(code)
+                .map_err(|err| Error::Generate(io::Error::new(io::ErrorKind::InvalidData, err)))?;
+            *components.entry("extensions").or_default() += Counts::of(&code);
+            e.blank().lines(code.lines());
+        }
+        components.retain(|_, counts| counts.lines > 0);
+
+        let mut total = Counts::default();
+        for counts in components.values() {
+            total += *counts;
         }
+        stats.push(DeclStats {
+            name: decl.name().to_owned(),
+            kind: layout.kind.name(),
+            total,
+            components,
+        });
         Ok(())
     }
 }
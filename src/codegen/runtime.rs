@@ -0,0 +1,99 @@
+//! The molecule runtime which the generated code relies on: the `Number` and `Byte` types and the
+//! helpers which read and write the header numbers.
+//!
+//! By default every generated package inlines the runtime as its prelude. It can also be generated
+//! as a package of its own, which the schema packages import, so the packages of one contract share
+//! a single `Byte` type.
+
+use super::emitter::Emitter;
+use crate::options::Options;
+
+/// The helpers of the runtime, by their names in the prelude and in the runtime package.
+const HELPERS: &[(&str, &str)] = &[
+    ("unpackNumber", "UnpackNumber"),
+    ("packNumber", "PackNumber"),
+];
+
+/// The name of a runtime helper, exported from the runtime package or private to the prelude.
+fn symbol(name: &str, exported: bool) -> &str {
+    HELPERS
+        .iter()
+        .find(|(prelude, _)| *prelude == name)
+        .map(|(prelude, package)| if exported { *package } else { *prelude })
+        .expect("the helper is part of the runtime")
+}
+
+/// Writes the runtime, exported for the runtime package or as the prelude of a schema package.
+pub(super) fn write_runtime(e: &mut Emitter, exported: bool) {
+    let unpack_number = symbol("unpackNumber", exported);
+    let pack_number = symbol("packNumber", exported);
+    e.blank();
+    e.line("type Number uint32");
+    e.line("const HeaderSizeUint = uint32(4)");
+    e.blank();
+    e.comment("Byte is the primitive type");
+    e.line("type Byte byte");
+    e.func("NewByte(b byte) Byte", |e| {
+        e.line("return b");
+    });
+    e.func("ByteDefault() Byte", |e| {
+        e.line("return Byte(0)");
+    });
+    e.func("ByteFromSliceUnchecked(slice []byte) Byte", |e| {
+        e.line("return slice[0]");
+    });
+    e.func("(b Byte) AsSlice() []byte", |e| {
+        e.line("return []byte{b}");
+    });
+    e.func(
+        "ByteFromSlice(slice []byte, _compatible bool) (b Byte, e error)",
+        |e| {
+            e.block("if len(slice) != uint32(1)", |e| {
+                e.line("return b, errors.New(\"TotalSizeNotMatch\")");
+            });
+            e.line("b = slice[0]");
+            e.line("return b, errors.None()");
+        },
+    );
+    e.func(format!("{}(b []byte) Number", unpack_number), |e| {
+        e.line("bytesBuffer := bytes.NewBuffer(b)");
+        e.line("x := binary.ReadUint32(bytesBuffer, binary.LittleEndian)");
+        e.line("return Number(x)");
+    });
+    e.func(format!("{}(num Number) []byte", pack_number), |e| {
+        e.line("b := make([]byte, 4)");
+        e.line("binary.LittleEndian.PutUint32(b, uint32(num))");
+        e.line("return b");
+    });
+}
+
+/// The names of the template variables which refer to the runtime, see [`variables`].
+pub(super) const VARIABLES: &[&str] = &["runtime", "unpack_number", "pack_number"];
+
+/// The values of the variables which every template has, so the code refers to the runtime
+/// through the runtime package when `runtime` is set: `runtime` is the package qualifier of the
+/// runtime types and constants, `unpack_number` and `pack_number` the names of the helpers.
+pub(super) fn variables(options: &Options) -> Vec<(&'static str, String)> {
+    let exported = options.runtime.is_some();
+    let prefix = qualifier(options);
+    vec![
+        ("runtime", prefix.clone()),
+        (
+            "unpack_number",
+            format!("{}{}", prefix, symbol("unpackNumber", exported)),
+        ),
+        (
+            "pack_number",
+            format!("{}{}", prefix, symbol("packNumber", exported)),
+        ),
+    ]
+}
+
+/// The qualifier of the runtime symbols, `<package>.` when the runtime is imported and empty when
+/// it's inlined.
+pub(super) fn qualifier(options: &Options) -> String {
+    match options.runtime {
+        Some(_) => format!("{}.", options.runtime_package()),
+        None => String::new(),
+    }
+}
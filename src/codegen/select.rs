@@ -0,0 +1,193 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use molecule_codegen::ast::{self, HasName};
+
+use crate::{
+    layout::{Kind, Layout},
+    options::Options,
+    Error,
+};
+
+/// Selects the declarations of the schema to generate.
+///
+/// Without `types`, every declaration of the schema itself is a root, and the declarations in
+/// `types` must be declared by the schema itself as well. The roots and everything
+/// they refer to are returned, with dependencies before their dependents. A declaration in
+/// `exclude` is dropped from the roots, and it's an error if a selected declaration still needs it.
+pub(crate) fn select_decls(
+    ast: &ast::Ast,
+    layout: &Layout,
+    options: &Options,
+) -> Result<Vec<Rc<ast::TopDecl>>, Error> {
+    let major_decls = ast.major_decls();
+    let all_decls = ast
+        .decls()
+        .iter()
+        .map(|decl| (decl.name(), decl))
+        .collect::<HashMap<_, _>>();
+    for name in options.types.iter().chain(options.exclude.iter()) {
+        if !all_decls.contains_key(name.as_str()) {
+            return Err(Error::Config(format!(
+                "the schema has no declaration named \"{}\"",
+                name
+            )));
+        }
+    }
+    // Declarations of the imported schemas are not generated here.
+    let major_names = major_decls
+        .iter()
+        .map(|decl| decl.name())
+        .collect::<HashSet<_>>();
+    for name in &options.types {
+        if !major_names.contains(name.as_str()) {
+            return Err(Error::Config(format!(
+                "\"{}\" is declared in an imported schema, generate it with that schema",
+                name
+            )));
+        }
+    }
+
+    let excluded = options
+        .exclude
+        .iter()
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+    let roots = if options.types.is_empty() {
+        major_decls
+            .iter()
+            .map(|decl| decl.name())
+            .collect::<Vec<_>>()
+    } else {
+        options.types.iter().map(String::as_str).collect()
+    };
+
+    let mut selected = HashSet::new();
+    let mut ordered = Vec::new();
+    for root in roots.into_iter().filter(|name| !excluded.contains(name)) {
+        visit(root, layout, &excluded, &mut selected, &mut ordered)?;
+    }
+
+    Ok(ordered
+        .into_iter()
+        .filter(|name| major_names.contains(name))
+        .map(|name| Rc::clone(all_decls[name]))
+        .collect())
+}
+
+/// Adds `name` and the declarations it refers to into `ordered`, dependencies first.
+///
+/// The roots are never excluded, so an excluded declaration is only ever reached as a reference,
+/// which is reported with the declaration that refers to it.
+fn visit<'a>(
+    name: &'a str,
+    layout: &'a Layout,
+    excluded: &HashSet<&str>,
+    selected: &mut HashSet<&'a str>,
+    ordered: &mut Vec<&'a str>,
+) -> Result<(), Error> {
+    let decl = layout
+        .get(name)
+        .expect("references are resolved by molecule");
+    if decl.kind == Kind::Primitive || !selected.insert(name) {
+        return Ok(());
+    }
+    for reference in &decl.references {
+        if excluded.contains(reference.as_str()) {
+            return Err(Error::Config(format!(
+                "\"{}\" is excluded, but \"{}\" depends on it",
+                reference, name
+            )));
+        }
+        visit(reference, layout, excluded, selected, ordered)?;
+    }
+    ordered.push(name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use molecule_codegen::IntermediateFormat;
+
+    use super::*;
+
+    /// A schema which imports `Byte32`, and declares `Scripts` before the declarations it needs.
+    fn schema() -> ast::Ast {
+        let ir = r#"{"namespace": "chain",
+            "imports": [{"name": "basic", "paths": [], "path_supers": 0}],
+            "declarations": [
+                {"type": "array", "name": "Byte32", "item": "byte", "item_count": 32,
+                    "imported_depth": 1},
+                {"type": "dynvec", "name": "Scripts", "item": "Script", "imported_depth": 0},
+                {"type": "fixvec", "name": "Bytes", "item": "byte", "imported_depth": 0},
+                {"type": "table", "name": "Script", "fields": [
+                    {"name": "code_hash", "type": "Byte32"},
+                    {"name": "args", "type": "Bytes"}
+                ], "imported_depth": 0},
+                {"type": "option", "name": "ScriptOpt", "item": "Script", "imported_depth": 0}
+            ]}"#;
+        IntermediateFormat::JSON.recover(ir.as_bytes()).unwrap()
+    }
+
+    fn select(types: &[&str], exclude: &[&str]) -> Result<Vec<String>, Error> {
+        let ast = schema();
+        let mut options = Options::default();
+        options.types = types.iter().map(|name| (*name).to_owned()).collect();
+        options.exclude = exclude.iter().map(|name| (*name).to_owned()).collect();
+        let decls = select_decls(&ast, &Layout::new(&ast), &options)?;
+        Ok(decls.iter().map(|decl| decl.name().to_owned()).collect())
+    }
+
+    fn config_error(result: Result<Vec<String>, Error>) -> String {
+        match result {
+            Err(Error::Config(message)) => message,
+            result => panic!("expected a configuration error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn dependencies_come_before_their_dependents() {
+        assert_eq!(
+            select(&[], &[]).unwrap(),
+            ["Bytes", "Script", "Scripts", "ScriptOpt"]
+        );
+        assert_eq!(
+            select(&["Scripts"], &[]).unwrap(),
+            ["Bytes", "Script", "Scripts"]
+        );
+    }
+
+    #[test]
+    fn excluded_declarations_are_not_roots() {
+        assert_eq!(
+            select(&[], &["Scripts", "ScriptOpt"]).unwrap(),
+            ["Bytes", "Script"]
+        );
+    }
+
+    #[test]
+    fn a_needed_declaration_can_not_be_excluded() {
+        assert_eq!(
+            config_error(select(&["Scripts"], &["Bytes"])),
+            "\"Bytes\" is excluded, but \"Script\" depends on it"
+        );
+        assert_eq!(
+            config_error(select(&[], &["Script"])),
+            "\"Script\" is excluded, but \"Scripts\" depends on it"
+        );
+    }
+
+    #[test]
+    fn imported_declarations_are_not_generated() {
+        assert_eq!(
+            config_error(select(&["Byte32"], &[])),
+            "\"Byte32\" is declared in an imported schema, generate it with that schema"
+        );
+        assert_eq!(
+            config_error(select(&["Hash"], &[])),
+            "the schema has no declaration named \"Hash\""
+        );
+    }
+}
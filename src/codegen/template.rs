@@ -0,0 +1,367 @@
+//! The named templates of the per-declaration code.
+//!
+//! The templates ship embedded in the binary, and any of them can be overridden by a file named
+//! `<name>.cell` in the template directory. A template is CellScript code with `{{variable}}`
+//! placeholders; the values are substituted as they are, and the formatter lays out the result.
+
+use std::{borrow::Cow, collections::HashMap, fs, path::Path};
+
+use super::runtime;
+use crate::Error;
+
+/// An embedded template, the component it belongs to and the variables which are available in it.
+struct Template {
+    name: &'static str,
+    /// The component in the statistics, see [`Stats`](crate::Stats).
+    component: &'static str,
+    source: &'static str,
+    variables: &'static [&'static str],
+}
+
+macro_rules! templates {
+    ($($name:literal in $component:ident: [$($variable:literal),*],)*) => {
+        &[$(Template {
+            name: $name,
+            component: stringify!($component),
+            source: include_str!(concat!("templates/", $name, ".cell")),
+            variables: &[$($variable),*],
+        },)*]
+    };
+}
+
+/// Keep in sync with the table in the README.
+const TEMPLATES: &[Template] = templates! {
+    "common" in common: ["struct_name", "default_content", "layout_doc"],
+    "option_builder" in builders: ["struct_name", "inner_type", "inner_name"],
+    "option_verification" in verification: ["struct_name", "inner_type", "inner_name"],
+    "option_readers" in readers: ["struct_name", "inner_type", "inner_name"],
+    "option_as_builder" in builders: ["struct_name", "inner_type", "inner_name"],
+    "union_builder" in builders: ["struct_name"],
+    "union_type" in readers: [
+        "struct_name", "union_name", "item_conversions", "item_name_cases", "to_union_cases"
+    ],
+    "union_item" in readers: ["union_name", "item_type", "item_name", "item_id"],
+    "union_verification" in verification: ["struct_name", "verify_cases"],
+    "union_readers" in readers: ["struct_name"],
+    "union_as_builder" in builders: ["struct_name"],
+    "array_builder" in builders: ["struct_name", "inner_type", "item_count", "default_items"],
+    "array_builder_nth" in builders: ["struct_name", "inner_type", "index"],
+    "fixed_verification" in verification: ["struct_name", "total_size"],
+    "array_raw_data" in readers: ["struct_name"],
+    "array_nth" in readers: ["struct_name", "inner_type", "index", "start", "end"],
+    "array_as_builder" in builders: ["struct_name", "item_copies"],
+    "struct_builder" in builders: ["struct_name", "builder_fields", "field_writes", "field_setters", "default_fields"],
+    "field_setter" in builders: ["struct_name", "func_name", "field_name", "field_type"],
+    "struct_getter" in readers: ["struct_name", "func_name", "field_type", "start", "end"],
+    "fields_as_builder" in builders: ["struct_name", "field_copies"],
+    "fixvec_builder" in builders: ["struct_name", "inner_type"],
+    "fixvec_verification" in verification: ["struct_name", "item_size"],
+    "fixvec_readers" in readers: ["struct_name", "inner_type", "item_size"],
+    "fixvec_raw_data" in readers: ["struct_name"],
+    "vector_as_builder" in builders: ["struct_name"],
+    "dynvec_builder" in builders: ["struct_name", "inner_type"],
+    "dynvec_verification" in verification: ["struct_name", "inner_type"],
+    "dynvec_readers" in readers: ["struct_name", "inner_type"],
+    "table_builder" in builders: [
+        "struct_name", "builder_fields", "field_count", "field_offsets", "field_writes", "field_setters",
+        "default_fields"
+    ],
+    "empty_table_builder" in builders: ["struct_name"],
+    "empty_table_new" in common: ["struct_name"],
+    "empty_table_verification" in verification: ["struct_name"],
+    "table_verification" in verification: ["struct_name", "field_count", "verify_fields"],
+    "table_readers" in readers: ["struct_name", "field_count"],
+    "table_getter" in readers: ["struct_name", "func_name", "field_type", "start", "end"],
+    "table_last_getter" in readers: ["struct_name", "func_name", "field_type", "start", "end"],
+    "alias" in common: ["struct_name", "canonical_type", "layout_doc"],
+    "alias_builder" in builders: ["struct_name", "canonical_type"],
+    "alias_verification" in verification: ["struct_name", "canonical_type"],
+    "alias_as_builder" in builders: ["struct_name", "canonical_type"],
+};
+
+/// The templates in use, the embedded ones with the overrides applied.
+#[derive(Debug)]
+pub(crate) struct Templates {
+    sources: HashMap<&'static str, Cow<'static, str>>,
+    /// The variables which every template has, see [`runtime::variables`].
+    runtime: Vec<(&'static str, String)>,
+}
+
+/// A part of a template.
+enum Piece<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+}
+
+impl Templates {
+    /// Loads the overrides from `dir` on top of the embedded templates.
+    ///
+    /// Only the `.cell` files of the directory are read, and each of them must be named after a
+    /// template and use only the variables of that template and the `runtime` ones, whose values
+    /// are given.
+    pub(crate) fn load(
+        dir: Option<&Path>,
+        runtime: Vec<(&'static str, String)>,
+    ) -> Result<Self, Error> {
+        let mut sources = TEMPLATES
+            .iter()
+            .map(|template| (template.name, Cow::Borrowed(template.source)))
+            .collect::<HashMap<_, _>>();
+        let dir = match dir {
+            Some(dir) => dir,
+            None => return Ok(Self { sources, runtime }),
+        };
+
+        let read_error = |path: &Path| {
+            let path = path.to_owned();
+            move |source| Error::Read {
+                path: Some(path),
+                source,
+            }
+        };
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir).map_err(read_error(dir))? {
+            let path = entry.map_err(read_error(dir))?.path();
+            if path.extension() == Some("cell".as_ref()) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let template = TEMPLATES
+                .iter()
+                .find(|template| template.name == name)
+                .ok_or_else(|| {
+                    Error::Config(format!(
+                        "{}: there is no template named \"{}\"",
+                        path.display(),
+                        name
+                    ))
+                })?;
+            let source = fs::read_to_string(&path).map_err(read_error(&path))?;
+            for piece in parse(&source) {
+                if let Piece::Variable(variable) = piece {
+                    if !template.variables.contains(&variable)
+                        && !runtime::VARIABLES.contains(&variable)
+                    {
+                        return Err(Error::Config(format!(
+                            "{}: the template \"{}\" has no variable \"{}\", the variables are: {}",
+                            path.display(),
+                            name,
+                            variable,
+                            template
+                                .variables
+                                .iter()
+                                .chain(runtime::VARIABLES)
+                                .copied()
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )));
+                    }
+                }
+            }
+            sources.insert(template.name, Cow::Owned(source));
+        }
+        Ok(Self { sources, runtime })
+    }
+
+    /// The component which the code of the template `name` belongs to.
+    pub(crate) fn component(name: &str) -> &'static str {
+        TEMPLATES
+            .iter()
+            .find(|template| template.name == name)
+            .map(|template| template.component)
+            .expect("the template is embedded")
+    }
+
+    /// Substitutes the variables of the template `name`.
+    ///
+    /// A line which holds nothing but a placeholder is dropped when the value is empty.
+    pub(crate) fn render(&self, name: &str, variables: &[(&str, String)]) -> String {
+        let source = self.sources.get(name).expect("the template is embedded");
+        let mut code = String::with_capacity(source.len());
+        let mut drop_newline = false;
+        for piece in parse(source) {
+            match piece {
+                Piece::Text(text) => {
+                    let text = match text.strip_prefix('\n') {
+                        Some(rest) if drop_newline => rest,
+                        _ => text,
+                    };
+                    code.push_str(text);
+                    drop_newline = false;
+                }
+                Piece::Variable(variable) => {
+                    let (_, value) = variables
+                        .iter()
+                        .chain(&self.runtime)
+                        .find(|(name, _)| *name == variable)
+                        .expect("the variables of the template are checked when it's loaded");
+                    let line_start = code.trim_end_matches([' ', '\t']).len();
+                    if value.is_empty() && (line_start == 0 || code[..line_start].ends_with('\n')) {
+                        code.truncate(line_start);
+                        drop_newline = true;
+                    }
+                    code.push_str(value);
+                }
+            }
+        }
+        code
+    }
+}
+
+/// Splits a template at the placeholders.
+///
+/// `{{` which doesn't start a placeholder is text, so `{{{items}}}` is a placeholder inside a
+/// composite literal.
+fn parse(source: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+    while let Some(found) = source[pos..].find("{{") {
+        let open = pos + found;
+        let rest = &source[open + 2..];
+        let placeholder = rest.find("}}").map(|close| &rest[..close]).filter(|inner| {
+            let inner = inner.trim();
+            !inner.is_empty()
+                && !inner.starts_with(|c: char| c.is_ascii_digit())
+                && inner.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        match placeholder {
+            Some(inner) => {
+                pieces.push(Piece::Text(&source[text_start..open]));
+                pieces.push(Piece::Variable(inner.trim()));
+                pos = open + 2 + inner.len() + 2;
+                text_start = pos;
+            }
+            None => pos = open + 1,
+        }
+    }
+    pieces.push(Piece::Text(&source[text_start..]));
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn render(name: &str, variables: &[(&str, String)]) -> String {
+        Templates::load(None, Vec::new())
+            .unwrap()
+            .render(name, variables)
+    }
+
+    /// Loads the templates from a directory which holds the `files`.
+    fn load(test: &str, files: &[(&str, &str)]) -> Result<Templates, Error> {
+        let dir = env::temp_dir().join(format!(
+            "moleculec-cellscript-templates-{}-{}",
+            test,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let templates = Templates::load(Some(&dir), runtime::variables(&Default::default()));
+        fs::remove_dir_all(&dir).unwrap();
+        templates
+    }
+
+    fn describe(pieces: &[Piece]) -> Vec<String> {
+        pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => format!("text {:?}", text),
+                Piece::Variable(variable) => format!("variable {}", variable),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_placeholder_may_sit_inside_a_composite_literal() {
+        assert_eq!(
+            describe(&parse("[]byte{{{items}}}")),
+            ["text \"[]byte{\"", "variable items", "text \"}\""]
+        );
+    }
+
+    #[test]
+    fn braces_without_a_variable_name_are_text() {
+        assert_eq!(
+            describe(&parse("{{}} {{1x}} {{a b}}")),
+            ["text \"{{}} {{1x}} {{a b}}\""]
+        );
+    }
+
+    #[test]
+    fn a_line_with_only_an_empty_placeholder_is_dropped() {
+        let code = render(
+            "array_as_builder",
+            &[
+                ("struct_name", "A".to_owned()),
+                ("item_copies", String::new()),
+            ],
+        );
+        assert_eq!(
+            code,
+            "func (s *A) AsBuilder() ABuilder {\n\tt := NewABuilder()\n\treturn t\n}\n"
+        );
+    }
+
+    #[test]
+    fn an_empty_placeholder_within_a_line_is_kept() {
+        let code = render(
+            "common",
+            &[
+                ("struct_name", "A".to_owned()),
+                ("default_content", String::new()),
+                ("layout_doc", "// A.".to_owned()),
+            ],
+        );
+        assert!(code.contains("return AFromSliceUnchecked([]byte{})\n"));
+    }
+
+    #[test]
+    fn overrides_replace_the_embedded_templates() {
+        let templates = load(
+            "override",
+            &[
+                (
+                    "array_raw_data.cell",
+                    "// raw {{struct_name}} {{runtime}}Number\n",
+                ),
+                ("notes.txt", "not a template"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            templates.render("array_raw_data", &[("struct_name", "A".to_owned())]),
+            "// raw A Number\n"
+        );
+    }
+
+    #[test]
+    fn an_unknown_template_is_an_error() {
+        let err = load("unknown-template", &[("nope.cell", "")]).unwrap_err();
+        assert!(matches!(err, Error::Config(ref message)
+            if message.ends_with("there is no template named \"nope\"")));
+    }
+
+    #[test]
+    fn an_unknown_variable_is_an_error() {
+        let err = load(
+            "unknown-variable",
+            &[("array_raw_data.cell", "{{inner_type}}")],
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Config(ref message)
+            if message.contains("the template \"array_raw_data\" has no variable \"inner_type\"")));
+    }
+}
@@ -0,0 +1,282 @@
+//! A minimal line-based unified diff, used to report drift in `--check` mode.
+
+use std::fmt::Write as _;
+
+/// Lines of unchanged context shown around each change.
+const CONTEXT: usize = 3;
+/// Above this edit distance, the diff gives up searching for the shortest edit script and reports
+/// the changed region as replaced wholesale. This keeps the memory bounded for unrelated files.
+const MAX_EDIT_DISTANCE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Returns the unified diff from `old` to `new`, or `None` if they have the same lines.
+pub(crate) fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let ops = diff_lines(&old_lines, &new_lines);
+    if ops.iter().all(|op| *op == Op::Equal) {
+        return None;
+    }
+
+    let mut output = String::new();
+    writeln!(output, "--- {}", old_name).unwrap();
+    writeln!(output, "+++ {}", new_name).unwrap();
+
+    // The positions in both files before each operation.
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            Op::Equal => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Op::Delete => old_pos += 1,
+            Op::Insert => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| **op != Op::Equal)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut hunk_start = 0;
+    while hunk_start < changes.len() {
+        let mut hunk_end = hunk_start;
+        while hunk_end + 1 < changes.len()
+            && changes[hunk_end + 1] - changes[hunk_end] <= 2 * CONTEXT + 1
+        {
+            hunk_end += 1;
+        }
+        let begin = changes[hunk_start].saturating_sub(CONTEXT);
+        let end = (changes[hunk_end] + 1 + CONTEXT).min(ops.len());
+        let (old_begin, new_begin) = positions[begin];
+        let (old_end, new_end) = positions[end];
+        writeln!(
+            output,
+            "@@ -{} +{} @@",
+            hunk_range(old_begin, old_end - old_begin),
+            hunk_range(new_begin, new_end - new_begin)
+        )
+        .unwrap();
+        for (op, (old_pos, new_pos)) in ops[begin..end].iter().zip(&positions[begin..end]) {
+            match op {
+                Op::Equal => writeln!(output, " {}", old_lines[*old_pos]),
+                Op::Delete => writeln!(output, "-{}", old_lines[*old_pos]),
+                Op::Insert => writeln!(output, "+{}", new_lines[*new_pos]),
+            }
+            .unwrap();
+        }
+        hunk_start = hunk_end + 1;
+    }
+    Some(output)
+}
+
+fn hunk_range(begin: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", begin),
+        1 => format!("{}", begin + 1),
+        _ => format!("{},{}", begin + 1, count),
+    }
+}
+
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![Op::Equal; prefix];
+    ops.extend(myers(old_middle, new_middle).unwrap_or_else(|| {
+        let mut replaced = vec![Op::Delete; old_middle.len()];
+        replaced.extend(vec![Op::Insert; new_middle.len()]);
+        replaced
+    }));
+    ops.extend(vec![Op::Equal; suffix]);
+    ops
+}
+
+/// Myers' O(ND) shortest edit script.
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<Op>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (old.len() + new.len()).min(MAX_EDIT_DISTANCE) as isize;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+
+    for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m, offset));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize, offset: isize) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let index = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+            x = prev_x;
+            y = prev_y;
+        }
+    }
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> Option<String> {
+        unified_diff(old, new, "old", "new")
+    }
+
+    /// The lines `1` to `count`.
+    fn numbered(count: usize) -> String {
+        (1..=count).map(|i| format!("{}\n", i)).collect()
+    }
+
+    /// The lines `1` to `count`, with some of them replaced, or deleted when the replacement is
+    /// `None`.
+    fn edited(count: usize, edits: &[(usize, Option<&str>)]) -> String {
+        (1..=count)
+            .filter_map(|i| match edits.iter().find(|(line, _)| *line == i) {
+                Some((_, replacement)) => replacement.map(|line| format!("{}\n", line)),
+                None => Some(format!("{}\n", i)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_input_has_no_diff() {
+        assert_eq!(diff("", ""), None);
+        assert_eq!(diff("a\nb\n", "a\nb\n"), None);
+    }
+
+    #[test]
+    fn insertion_at_the_start() {
+        assert_eq!(
+            diff(&numbered(5), &format!("0\n{}", numbered(5))).unwrap(),
+            "--- old\n+++ new\n@@ -1,3 +1,4 @@\n+0\n 1\n 2\n 3\n"
+        );
+    }
+
+    #[test]
+    fn insertion_at_the_end() {
+        assert_eq!(
+            diff(&numbered(5), &format!("{}6\n", numbered(5))).unwrap(),
+            "--- old\n+++ new\n@@ -3,3 +3,4 @@\n 3\n 4\n 5\n+6\n"
+        );
+    }
+
+    #[test]
+    fn deletion() {
+        let new = edited(10, &[(5, None)]);
+        assert_eq!(
+            diff(&numbered(10), &new).unwrap(),
+            "--- old\n+++ new\n@@ -2,7 +2,6 @@\n 2\n 3\n 4\n-5\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn empty_ranges_start_before_the_change() {
+        assert_eq!(
+            diff("", "a\n").unwrap(),
+            "--- old\n+++ new\n@@ -0,0 +1 @@\n+a\n"
+        );
+        assert_eq!(
+            diff("a\n", "").unwrap(),
+            "--- old\n+++ new\n@@ -1 +0,0 @@\n-a\n"
+        );
+    }
+
+    #[test]
+    fn changes_within_twice_the_context_share_a_hunk() {
+        // Six unchanged lines between the changes are covered by the context of both.
+        let new = edited(20, &[(5, Some("five")), (12, Some("twelve"))]);
+        assert_eq!(
+            diff(&numbered(20), &new).unwrap(),
+            "--- old\n+++ new\n@@ -2,14 +2,14 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n 9\n 10\n 11\n\
+             -12\n+twelve\n 13\n 14\n 15\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_separate_hunks() {
+        // Seven unchanged lines between the changes leave one line out of both contexts.
+        let new = edited(20, &[(5, Some("five")), (13, Some("thirteen"))]);
+        let diff = diff(&numbered(20), &new).unwrap();
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -2,7 +2,7 @@\n"));
+        assert!(diff.contains("@@ -10,7 +10,7 @@\n"));
+    }
+
+    #[test]
+    fn above_the_edit_limit_the_region_is_replaced() {
+        let old = (0..600).map(|i| format!("old {}", i)).collect::<Vec<_>>();
+        let new = (0..600).map(|i| format!("new {}", i)).collect::<Vec<_>>();
+        let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+        let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(myers(&old, &new), None);
+
+        let ops = diff_lines(&["same"], &["same"])
+            .into_iter()
+            .chain(diff_lines(&old, &new))
+            .collect::<Vec<_>>();
+        let mut expected = vec![Op::Equal];
+        expected.extend(vec![Op::Delete; 600]);
+        expected.extend(vec![Op::Insert; 600]);
+        assert_eq!(ops, expected);
+
+        let diff = diff(&old.join("\n"), &new.join("\n")).unwrap();
+        assert!(diff.starts_with("--- old\n+++ new\n@@ -1,600 +1,600 @@\n-old 0\n"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Exit code for `--check` when the checked file doesn't match the generated code.
+pub const EXIT_STALE: i32 = 1;
+/// Exit code for a failure to read the input data or the schema file.
+pub const EXIT_READ: i32 = 66;
+/// Exit code for malformed input: the intermediate data or the schema can't be recovered.
+pub const EXIT_RECOVER: i32 = 65;
+/// Exit code for a failure inside the code generator.
+pub const EXIT_GENERATE: i32 = 70;
+/// Exit code for a failure to write the generated code.
+pub const EXIT_WRITE: i32 = 74;
+/// Exit code for an invalid configuration.
+pub const EXIT_CONFIG: i32 = 78;
+
+/// Errors returned by the plugin driver.
+#[derive(Debug)]
+pub enum Error {
+    /// The input couldn't be read. `path` is `None` for stdin.
+    Read {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// The input was read, but it is not a valid intermediate data or schema.
+    Recover(String),
+    /// The generator failed to produce the code.
+    Generate(io::Error),
+    /// The generated code couldn't be written. `path` is `None` for stdout.
+    Write {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// The checked file is out of date with the generated code.
+    Stale(PathBuf),
+    /// The configuration is invalid.
+    Config(String),
+}
+
+impl Error {
+    /// The process exit code which represents this error.
+    ///
+    /// The values follow `sysexits.h`, so scripts can tell a bad schema apart from an I/O failure.
+    /// `EXIT_STALE` mirrors `diff`, which exits with 1 when the inputs differ.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Read { .. } => EXIT_READ,
+            Self::Recover(_) => EXIT_RECOVER,
+            Self::Generate(_) => EXIT_GENERATE,
+            Self::Write { .. } => EXIT_WRITE,
+            Self::Stale(_) => EXIT_STALE,
+            Self::Config(_) => EXIT_CONFIG,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Read {
+                path: Some(path),
+                source,
+            } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            Self::Read { path: None, source } => {
+                write!(f, "failed to read data from stdin: {}", source)
+            }
+            Self::Recover(message) => write!(f, "failed to recover the schema: {}", message),
+            Self::Generate(source) => write!(f, "failed to write data by generator: {}", source),
+            Self::Write {
+                path: Some(path),
+                source,
+            } => {
+                write!(f, "failed to write {}: {}", path.display(), source)
+            }
+            Self::Write { path: None, source } => {
+                write!(f, "failed to write data to stdout: {}", source)
+            }
+            Self::Stale(path) => write!(
+                f,
+                "{} is not up to date with the generated code",
+                path.display()
+            ),
+            Self::Config(message) => write!(f, "invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { source, .. } | Self::Generate(source) | Self::Write { source, .. } => {
+                Some(source)
+            }
+            Self::Recover(_) | Self::Stale(_) | Self::Config(_) => None,
+        }
+    }
+}
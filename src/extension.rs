@@ -0,0 +1,71 @@
+//! Hooks which append custom code to the generated declarations.
+
+use std::{fmt, io, sync::Arc};
+
+use molecule_codegen::ast;
+
+use crate::layout::DeclLayout;
+
+/// Appends code to the generated declarations, e.g. domain specific validators.
+///
+/// An extension is called after the code of each declaration is emitted, and whatever it writes
+/// follows that code. The output is laid out by the formatter like the rest of the generated code,
+/// and in the split output it goes into the file of the declaration.
+///
+/// ```no_run
+/// use std::io;
+///
+/// use moleculec_cellscript::{ast, layout::DeclLayout, Extension, Options};
+///
+/// struct ScriptValidator;
+///
+/// impl Extension for ScriptValidator {
+///     fn after_decl(
+///         &self,
+///         decl: &ast::TopDecl,
+///         _layout: &DeclLayout,
+///         writer: &mut dyn io::Write,
+///     ) -> io::Result<()> {
+///         if ast::HasName::name(decl) == "Script" {
+///             writeln!(writer, "func (s *Script) IsTypeScript() bool {{")?;
+///             writeln!(writer, "return s.HashType().AsSlice()[0] == 1")?;
+///             writeln!(writer, "}}")?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut options = Options::default();
+/// options.add_extension(ScriptValidator);
+/// ```
+pub trait Extension: Send + Sync {
+    /// Writes the code which follows the declaration `decl`.
+    ///
+    /// Failures are reported as [`Error::Generate`](crate::Error::Generate).
+    fn after_decl(
+        &self,
+        decl: &ast::TopDecl,
+        layout: &DeclLayout,
+        writer: &mut dyn io::Write,
+    ) -> io::Result<()>;
+}
+
+/// The registered extensions, in the order they're called.
+#[derive(Clone, Default)]
+pub(crate) struct Extensions(Vec<Arc<dyn Extension>>);
+
+impl Extensions {
+    pub(crate) fn push<E: Extension + 'static>(&mut self, extension: E) {
+        self.0.push(Arc::new(extension));
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &dyn Extension> {
+        self.0.iter().map(|extension| extension.as_ref())
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Extensions({})", self.0.len())
+    }
+}
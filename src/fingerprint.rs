@@ -0,0 +1,74 @@
+//! A stable hash of the schema, which the generated code can carry in its header.
+
+use std::fmt::Write as _;
+
+use molecule_codegen::ast::{self, HasName};
+use sha2::{Digest, Sha256};
+
+/// Hashes the declarations of the schema into `sha256:<hex>`.
+///
+/// Only the shape of the schema is hashed: the namespace and the name, kind, items, fields and item
+/// IDs of every declaration, in the order of the schema. Comments, formatting and the versions of
+/// molecule and the plugin don't change the fingerprint.
+pub(crate) fn schema_fingerprint(ast: &ast::Ast) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(describe(ast));
+    let mut fingerprint = String::from("sha256:");
+    for byte in hasher.finalize() {
+        write!(fingerprint, "{:02x}", byte).expect("writing into a string never fails");
+    }
+    fingerprint
+}
+
+/// The canonical text which is hashed, one line for the namespace and one for each declaration.
+fn describe(ast: &ast::Ast) -> String {
+    let mut text = format!("namespace {}\n", ast.namespace());
+    for decl in ast.decls() {
+        text.push_str(&describe_decl(decl));
+        text.push('\n');
+    }
+    text
+}
+
+/// The shape of a declaration in one line: its name, kind, items, fields and item IDs.
+pub(crate) fn describe_decl(decl: &ast::TopDecl) -> String {
+    let (kind, shape) = shape(decl);
+    format!("{} {}{}", kind, decl.name(), shape)
+}
+
+/// The shape of a declaration without its name. Declarations of the same shape have the same
+/// layout.
+pub(crate) fn describe_shape(decl: &ast::TopDecl) -> String {
+    let (kind, shape) = shape(decl);
+    format!("{}{}", kind, shape)
+}
+
+fn shape(decl: &ast::TopDecl) -> (&'static str, String) {
+    match decl {
+        ast::TopDecl::Primitive(inner) => ("primitive", format!(" {}", inner.size())),
+        ast::TopDecl::Option_(inner) => ("option", format!(" {}", inner.item().typ().name())),
+        ast::TopDecl::Union(inner) => {
+            let items = inner
+                .items()
+                .iter()
+                .map(|item| format!(" {}={}", item.typ().name(), item.id()))
+                .collect::<String>();
+            ("union", items)
+        }
+        ast::TopDecl::Array(inner) => (
+            "array",
+            format!(" {} {}", inner.item().typ().name(), inner.item_count()),
+        ),
+        ast::TopDecl::Struct(inner) => ("struct", fields(inner.fields())),
+        ast::TopDecl::FixVec(inner) => ("fixvec", format!(" {}", inner.item().typ().name())),
+        ast::TopDecl::DynVec(inner) => ("dynvec", format!(" {}", inner.item().typ().name())),
+        ast::TopDecl::Table(inner) => ("table", fields(inner.fields())),
+    }
+}
+
+fn fields(fields: &[ast::FieldDecl]) -> String {
+    fields
+        .iter()
+        .map(|field| format!(" {}:{}", field.name(), field.typ().name()))
+        .collect()
+}
@@ -0,0 +1,269 @@
+//! Identifiers of the generated CellScript code.
+//!
+//! The names of the schema become identifiers of the generated code. A name which is a keyword or a
+//! predeclared identifier of CellScript is escaped with a trailing underscore, so a field `type` is
+//! generated as `type_`, as a schema would spell it by hand. The getters and setters of the fields
+//! are escaped alike when they'd redefine a method of the generated code, e.g. `Len_` for `len`.
+
+use std::fmt;
+
+/// The keywords of CellScript, which can't be used as identifiers.
+pub(crate) const KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "chan",
+    "const",
+    "continue",
+    "default",
+    "defer",
+    "else",
+    "fallthrough",
+    "for",
+    "func",
+    "go",
+    "goto",
+    "if",
+    "import",
+    "interface",
+    "map",
+    "package",
+    "range",
+    "return",
+    "select",
+    "struct",
+    "switch",
+    "type",
+    "var",
+];
+
+/// The predeclared identifiers of CellScript. Declaring them again shadows the builtins which the
+/// generated code uses.
+pub(crate) const PREDECLARED: &[&str] = &[
+    "any",
+    "append",
+    "bool",
+    "byte",
+    "cap",
+    "clear",
+    "close",
+    "comparable",
+    "complex",
+    "complex128",
+    "complex64",
+    "copy",
+    "delete",
+    "error",
+    "false",
+    "float32",
+    "float64",
+    "imag",
+    "int",
+    "int16",
+    "int32",
+    "int64",
+    "int8",
+    "iota",
+    "len",
+    "make",
+    "max",
+    "min",
+    "new",
+    "nil",
+    "panic",
+    "print",
+    "println",
+    "real",
+    "recover",
+    "rune",
+    "string",
+    "true",
+    "uint",
+    "uint16",
+    "uint32",
+    "uint64",
+    "uint8",
+    "uintptr",
+];
+
+/// The methods which the generated code defines, which the getters and setters of the fields
+/// mustn't redefine.
+pub(crate) const GENERATED_METHODS: &[&str] = &[
+    "AsBuilder",
+    "AsSlice",
+    "Build",
+    "CountExtraFields",
+    "Extend",
+    "FieldCount",
+    "HasExtraFields",
+    "IsEmpty",
+    "IsNone",
+    "IsSome",
+    "ItemCount",
+    "ItemID",
+    "ItemName",
+    "Len",
+    "Push",
+    "RawData",
+    "Replace",
+    "Set",
+    "ToUnion",
+    "TotalSize",
+];
+
+/// The suffix which escapes a reserved name.
+pub(crate) const ESCAPE_SUFFIX: &str = "_";
+
+/// Checks whether `name` is a legal CellScript identifier: a letter or an underscore followed by
+/// letters, digits and underscores, which is neither a keyword nor the blank identifier.
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_well = chars
+        .next()
+        .map(|c| c == '_' || c.is_alphabetic())
+        .unwrap_or(false);
+    starts_well
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+        && name != "_"
+        && !KEYWORDS.contains(&name)
+}
+
+/// Escapes `name` if it's a keyword or a predeclared identifier.
+pub(crate) fn escape(name: String) -> String {
+    escape_as(name, Use::Identifier)
+}
+
+/// Escapes the name of a getter or a setter if it's a method of the generated code.
+pub(crate) fn escape_method(name: String) -> String {
+    escape_as(name, Use::Method)
+}
+
+/// How a name is used in the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Use {
+    Identifier,
+    Method,
+}
+
+fn escape_as(name: String, usage: Use) -> String {
+    if reason(&name, usage).is_some() {
+        name + ESCAPE_SUFFIX
+    } else {
+        name
+    }
+}
+
+/// Why `name` can't be used as it is, if it can't.
+fn reason(name: &str, usage: Use) -> Option<&'static str> {
+    match usage {
+        Use::Identifier if KEYWORDS.contains(&name) => Some("a keyword in CellScript"),
+        Use::Identifier if PREDECLARED.contains(&name) => Some("predeclared in CellScript"),
+        Use::Method if GENERATED_METHODS.contains(&name) => Some("a method of the generated code"),
+        _ => None,
+    }
+}
+
+/// A name of the schema which is escaped in the generated code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Rename {
+    /// The declaration in the schema.
+    pub decl: String,
+    /// The field of the declaration, unless the type name itself is escaped.
+    pub field: Option<String>,
+    /// The identifier which would be generated.
+    pub name: String,
+    /// The identifier which is generated instead.
+    pub escaped: String,
+    reason: &'static str,
+}
+
+impl Rename {
+    /// The rename of the identifier `name`, which is generated for `decl` or its `field`, if it's
+    /// escaped.
+    pub(crate) fn of(decl: &str, field: Option<&str>, name: String) -> Option<Self> {
+        Self::of_use(decl, field, name, Use::Identifier)
+    }
+
+    /// The rename of the getter or setter `name` of `field`, if it's escaped.
+    pub(crate) fn of_method(decl: &str, field: &str, name: String) -> Option<Self> {
+        Self::of_use(decl, Some(field), name, Use::Method)
+    }
+
+    fn of_use(decl: &str, field: Option<&str>, name: String, usage: Use) -> Option<Self> {
+        let reason = reason(&name, usage)?;
+        Some(Self {
+            decl: decl.to_owned(),
+            field: field.map(ToOwned::to_owned),
+            escaped: escape_as(name.clone(), usage),
+            name,
+            reason,
+        })
+    }
+}
+
+impl fmt::Display for Rename {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.field {
+            Some(ref field) => write!(f, "the field \"{}\" of \"{}\"", field, self.decl)?,
+            None => write!(f, "the declaration \"{}\"", self.decl)?,
+        }
+        write!(
+            f,
+            " is generated as `{}`, `{}` is {}",
+            self.escaped, self.name, self.reason
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_and_predeclared_identifiers_are_escaped() {
+        assert_eq!(escape("type".to_owned()), "type_");
+        assert_eq!(escape("len".to_owned()), "len_");
+        assert_eq!(escape("Type".to_owned()), "Type");
+        assert_eq!(escape("lock".to_owned()), "lock");
+    }
+
+    #[test]
+    fn generated_methods_are_escaped_as_methods_only() {
+        assert_eq!(escape_method("Len".to_owned()), "Len_");
+        assert_eq!(escape_method("AsSlice".to_owned()), "AsSlice_");
+        assert_eq!(escape_method("Lock".to_owned()), "Lock");
+        assert_eq!(escape("Len".to_owned()), "Len");
+        assert_eq!(escape_method("type".to_owned()), "type");
+    }
+
+    #[test]
+    fn renames_tell_why_a_name_is_escaped() {
+        let rename = Rename::of("Script", Some("type"), "type".to_owned()).unwrap();
+        assert_eq!(rename.escaped, "type_");
+        assert_eq!(
+            rename.to_string(),
+            "the field \"type\" of \"Script\" is generated as `type_`, `type` is a keyword in \
+             CellScript"
+        );
+
+        let rename = Rename::of_method("Header", "total_size", "TotalSize".to_owned()).unwrap();
+        assert_eq!(
+            rename.to_string(),
+            "the field \"total_size\" of \"Header\" is generated as `TotalSize_`, `TotalSize` is \
+             a method of the generated code"
+        );
+
+        let rename = Rename::of("string", None, "string".to_owned()).unwrap();
+        assert_eq!(
+            rename.to_string(),
+            "the declaration \"string\" is generated as `string_`, `string` is predeclared in \
+             CellScript"
+        );
+    }
+
+    #[test]
+    fn names_which_are_free_are_not_renamed() {
+        assert!(Rename::of("Script", Some("args"), "args".to_owned()).is_none());
+        assert!(Rename::of_method("Script", "args", "Args".to_owned()).is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, Read},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+use molecule_codegen::{
+    ast::{self, HasName},
+    IntermediateFormat, Parser,
+};
+
+use crate::Error;
+
+/// The format of the intermediate data which is read by the plugin.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum InputFormat {
+    Fixed(IntermediateFormat),
+    /// Detect the format from the data itself.
+    Auto,
+}
+
+impl InputFormat {
+    pub(crate) const POSSIBLE_VALUES: [&'static str; 3] = ["json", "yaml", "auto"];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Fixed(IntermediateFormat::JSON)),
+            "yaml" => Some(Self::Fixed(IntermediateFormat::YAML)),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn resolve(self, data: &[u8]) -> IntermediateFormat {
+        match self {
+            Self::Fixed(format) => format,
+            Self::Auto => detect_format(data),
+        }
+    }
+}
+
+/// Sniffs the intermediate format: JSON data always starts with an object or an array, anything
+/// else is treated as YAML.
+fn detect_format(data: &[u8]) -> IntermediateFormat {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    match data.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') | Some(b'[') => IntermediateFormat::JSON,
+        _ => IntermediateFormat::YAML,
+    }
+}
+
+/// Recovers the AST from the intermediate data.
+///
+/// molecule panics on data which parses but doesn't resolve, e.g. a field of an unknown type, so
+/// the panic is caught and turned into `Error::Recover` as well.
+pub(crate) fn recover_intermediate(data: &[u8], format: InputFormat) -> Result<ast::Ast, Error> {
+    let format = format.resolve(data);
+    catch_panic(|| format.recover(data))
+        .map_err(Error::Recover)
+        .and_then(|result| result.map_err(Error::Recover))
+}
+
+pub(crate) fn read_input(input_file: Option<&Path>) -> Result<Vec<u8>, Error> {
+    match input_file {
+        Some(path) => fs::read(path).map_err(|source| Error::Read {
+            path: Some(path.to_owned()),
+            source,
+        }),
+        None => {
+            let mut input = Vec::new();
+            io::stdin()
+                .read_to_end(&mut input)
+                .map_err(|source| Error::Read { path: None, source })?;
+            Ok(input)
+        }
+    }
+}
+
+/// Compiles the schema file with molecule's own parser.
+///
+/// The parser reports invalid schemas by panicking, so the panic is caught and turned into
+/// `Error::Recover` instead of tearing down the caller.
+pub(crate) fn parse_schema_file(schema_file: &Path) -> Result<ast::Ast, Error> {
+    if let Err(source) = fs::metadata(schema_file) {
+        return Err(Error::Read {
+            path: Some(schema_file.to_owned()),
+            source,
+        });
+    }
+    catch_panic(|| Parser::parse(&schema_file))
+        .map_err(|message| Error::Recover(format!("{}: {}", schema_file.display(), message)))
+}
+
+thread_local! {
+    /// Whether the panics of this thread are caught by [`catch_panic`], and not reported.
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f`, which is a part of molecule that reports errors by panicking, and turns its panic
+/// into the message of the panic.
+///
+/// The first call wraps the panic hook of the process, so the hook doesn't report the panics
+/// which are caught here. The panics of other threads are reported as before, and the hook is
+/// never swapped back, so concurrent calls don't race.
+fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    static WRAP_HOOK: Once = Once::new();
+    WRAP_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING_PANICS.with(Cell::get) {
+                hook(info);
+            }
+        }));
+    });
+
+    let catching = CATCHING_PANICS.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.with(|flag| flag.set(catching));
+    result.map_err(|payload| panic_message(payload.as_ref()))
+}
+
+/// Finds the namespace of every imported declaration by compiling the imported schema files.
+///
+/// The AST of a schema holds the imported declarations, but not the schemas they come from. The
+/// imports are followed transitively, and each declaration belongs to the schema which declares it.
+pub(crate) fn imported_namespaces(
+    schema_file: &Path,
+    ast: &ast::Ast,
+) -> Result<BTreeMap<String, String>, Error> {
+    let mut namespaces = BTreeMap::new();
+    let mut pending = ast
+        .imports()
+        .iter()
+        .map(|import| import_file(schema_file, import))
+        .collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    while let Some(file) = pending.pop() {
+        if !visited.insert(fs::canonicalize(&file).unwrap_or_else(|_| file.clone())) {
+            continue;
+        }
+        let imported = parse_schema_file(&file)?;
+        for decl in imported.major_decls() {
+            namespaces.insert(decl.name().to_owned(), imported.namespace().to_owned());
+        }
+        pending.extend(
+            imported
+                .imports()
+                .iter()
+                .map(|import| import_file(&file, import)),
+        );
+    }
+    Ok(namespaces)
+}
+
+/// The file which an import statement of `schema_file` refers to, as molecule resolves it.
+fn import_file(schema_file: &Path, import: &ast::ImportStmt) -> PathBuf {
+    let mut file = schema_file
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .to_owned();
+    for _ in 0..import.path_supers() {
+        file.push("..");
+    }
+    for segment in import.paths() {
+        file.push(segment);
+    }
+    file.push(import.name());
+    file.set_extension("mol");
+    file
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown error".to_owned()
+    }
+}
@@ -0,0 +1,308 @@
+//! The byte layout of the declarations of a schema.
+//!
+//! The layout is computed once from the AST. The code generator takes the sizes and offsets from
+//! it, and other tools can use it to inspect the encoding of a schema:
+//!
+//! ```no_run
+//! use moleculec_cellscript::{layout::Layout, parse_schema_file};
+//!
+//! let ast = parse_schema_file("blockchain.mol".as_ref())?;
+//! let layout = Layout::new(&ast);
+//! for decl in layout.decls().iter().filter(|decl| decl.is_fixed()) {
+//!     println!("{}: {} bytes", decl.name, decl.total_size.unwrap());
+//! }
+//! # Ok::<(), moleculec_cellscript::Error>(())
+//! ```
+
+use std::{collections::HashMap, ops::Range};
+
+use molecule_codegen::ast::{self, HasName};
+
+/// The size of the numbers in the encoding: item counts, total sizes and offsets.
+pub const NUMBER_SIZE: usize = 4;
+
+/// The layouts of all declarations of a schema, including the imported ones and the primitive
+/// types they refer to.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    decls: Vec<DeclLayout>,
+    index: HashMap<String, usize>,
+}
+
+/// The kinds of declarations in a schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Primitive,
+    Option,
+    Union,
+    Array,
+    Struct,
+    FixVec,
+    DynVec,
+    Table,
+}
+
+/// The layout of one declaration.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DeclLayout {
+    /// The name in the schema.
+    pub name: String,
+    pub kind: Kind,
+    /// The size of a fixed size declaration, `None` for the dynamic ones.
+    pub total_size: Option<usize>,
+    /// The item of an option, an array or a vector.
+    pub item: Option<ItemLayout>,
+    /// The number of items of an array.
+    pub item_count: Option<usize>,
+    /// The fields of a struct or a table.
+    pub fields: Vec<FieldLayout>,
+    /// The items of a union.
+    pub union_items: Vec<UnionItemLayout>,
+    /// 0 for the declarations of the schema itself, more for the imported ones.
+    pub imported_depth: usize,
+    /// The names of the declarations which this one refers to, without duplicates.
+    pub references: Vec<String>,
+}
+
+/// The item of an option, an array or a vector.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ItemLayout {
+    /// The name of the item type.
+    pub typ: String,
+    /// The size of a fixed size item.
+    pub size: Option<usize>,
+}
+
+/// A field of a struct or a table.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FieldLayout {
+    pub name: String,
+    /// The name of the field type.
+    pub typ: String,
+    /// The size of a fixed size field.
+    pub size: Option<usize>,
+    pub offset: FieldOffset,
+}
+
+/// Where the data of a field is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldOffset {
+    /// The field of a struct occupies this byte range of the struct.
+    Fixed(Range<usize>),
+    /// The field of a table starts at the offset stored at this position of the header, and ends
+    /// at the offset stored after it, or at the end of the table for the last field.
+    Header(usize),
+}
+
+/// An item of a union.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct UnionItemLayout {
+    /// The name of the item type.
+    pub typ: String,
+    /// The item ID which is written before the data.
+    pub id: usize,
+    /// The size of a fixed size item.
+    pub size: Option<usize>,
+}
+
+impl Layout {
+    /// Computes the layout of every declaration in the schema.
+    pub fn new(ast: &ast::Ast) -> Self {
+        let mut decls = Vec::with_capacity(ast.decls().len() + 1);
+        let mut index = HashMap::with_capacity(ast.decls().len() + 1);
+        for decl in ast.decls() {
+            // Primitive types are not declared in the schema, they're added before their first use.
+            for reference in references(decl) {
+                if let ast::TopDecl::Primitive(_) = reference.as_ref() {
+                    if !index.contains_key(reference.name()) {
+                        index.insert(reference.name().to_owned(), decls.len());
+                        decls.push(DeclLayout::new(reference));
+                    }
+                }
+            }
+            index.insert(decl.name().to_owned(), decls.len());
+            decls.push(DeclLayout::new(decl));
+        }
+        Self { decls, index }
+    }
+
+    /// The declarations, in the order of the schema.
+    pub fn decls(&self) -> &[DeclLayout] {
+        &self.decls
+    }
+
+    /// The layout of the declaration named `name` in the schema.
+    pub fn get(&self, name: &str) -> Option<&DeclLayout> {
+        self.index.get(name).map(|i| &self.decls[*i])
+    }
+}
+
+impl Kind {
+    /// The name of the kind in the molecule specification, e.g. `fixvec`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Primitive => "primitive",
+            Self::Option => "option",
+            Self::Union => "union",
+            Self::Array => "array",
+            Self::Struct => "struct",
+            Self::FixVec => "fixvec",
+            Self::DynVec => "dynvec",
+            Self::Table => "table",
+        }
+    }
+
+    /// Whether the declarations of this kind have a fixed size.
+    pub fn is_fixed(self) -> bool {
+        matches!(self, Self::Primitive | Self::Array | Self::Struct)
+    }
+}
+
+impl DeclLayout {
+    fn new(decl: &ast::TopDecl) -> Self {
+        let mut layout = Self {
+            name: decl.name().to_owned(),
+            kind: Kind::Primitive,
+            total_size: fixed_size(decl),
+            item: None,
+            item_count: None,
+            fields: Vec::new(),
+            union_items: Vec::new(),
+            imported_depth: 0,
+            references: Vec::new(),
+        };
+        for reference in references(decl) {
+            if !layout
+                .references
+                .iter()
+                .any(|name| name == reference.name())
+            {
+                layout.references.push(reference.name().to_owned());
+            }
+        }
+        match decl {
+            ast::TopDecl::Primitive(_) => {}
+            ast::TopDecl::Option_(inner) => {
+                layout.kind = Kind::Option;
+                layout.item = Some(ItemLayout::new(inner.item()));
+                layout.imported_depth = inner.imported_depth();
+            }
+            ast::TopDecl::Union(inner) => {
+                layout.kind = Kind::Union;
+                layout.union_items = inner
+                    .items()
+                    .iter()
+                    .map(|item| UnionItemLayout {
+                        typ: item.typ().name().to_owned(),
+                        id: item.id(),
+                        size: fixed_size(item.typ()),
+                    })
+                    .collect();
+                layout.imported_depth = inner.imported_depth();
+            }
+            ast::TopDecl::Array(inner) => {
+                layout.kind = Kind::Array;
+                layout.item = Some(ItemLayout::new(inner.item()));
+                layout.item_count = Some(inner.item_count());
+                layout.imported_depth = inner.imported_depth();
+            }
+            ast::TopDecl::Struct(inner) => {
+                layout.kind = Kind::Struct;
+                let mut offset = 0;
+                for (field, size) in inner.fields().iter().zip(inner.field_sizes()) {
+                    layout.fields.push(FieldLayout {
+                        name: field.name().to_owned(),
+                        typ: field.typ().name().to_owned(),
+                        size: Some(*size),
+                        offset: FieldOffset::Fixed(offset..offset + size),
+                    });
+                    offset += size;
+                }
+                layout.imported_depth = inner.imported_depth();
+            }
+            ast::TopDecl::FixVec(inner) => {
+                layout.kind = Kind::FixVec;
+                layout.item = Some(ItemLayout::new(inner.item()));
+                layout.imported_depth = inner.imported_depth();
+            }
+            ast::TopDecl::DynVec(inner) => {
+                layout.kind = Kind::DynVec;
+                layout.item = Some(ItemLayout::new(inner.item()));
+                layout.imported_depth = inner.imported_depth();
+            }
+            ast::TopDecl::Table(inner) => {
+                layout.kind = Kind::Table;
+                layout.fields = inner
+                    .fields()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| FieldLayout {
+                        name: field.name().to_owned(),
+                        typ: field.typ().name().to_owned(),
+                        size: fixed_size(field.typ()),
+                        // The header starts with the total size.
+                        offset: FieldOffset::Header((i + 1) * NUMBER_SIZE),
+                    })
+                    .collect();
+                layout.imported_depth = inner.imported_depth();
+            }
+        }
+        layout
+    }
+
+    /// Whether the declaration has a fixed size.
+    pub fn is_fixed(&self) -> bool {
+        self.kind.is_fixed()
+    }
+
+    /// The item size of an array or a vector of fixed size items.
+    pub fn item_size(&self) -> Option<usize> {
+        self.item.as_ref().and_then(|item| item.size)
+    }
+
+    /// The byte range of the `index`th item of an array.
+    pub fn item_range(&self, index: usize) -> Option<Range<usize>> {
+        match (self.kind, self.item_size(), self.item_count) {
+            (Kind::Array, Some(size), Some(count)) if index < count => {
+                Some(size * index..size * (index + 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ItemLayout {
+    fn new(item: &ast::ItemDecl) -> Self {
+        Self {
+            typ: item.typ().name().to_owned(),
+            size: fixed_size(item.typ()),
+        }
+    }
+}
+
+/// The declarations which a declaration refers to through its items, fields or union members.
+fn references(decl: &ast::TopDecl) -> Vec<&std::rc::Rc<ast::TopDecl>> {
+    match decl {
+        ast::TopDecl::Primitive(_) => Vec::new(),
+        ast::TopDecl::Option_(inner) => vec![inner.item().typ()],
+        ast::TopDecl::Union(inner) => inner.items().iter().map(|item| item.typ()).collect(),
+        ast::TopDecl::Array(inner) => vec![inner.item().typ()],
+        ast::TopDecl::Struct(inner) => inner.fields().iter().map(|field| field.typ()).collect(),
+        ast::TopDecl::FixVec(inner) => vec![inner.item().typ()],
+        ast::TopDecl::DynVec(inner) => vec![inner.item().typ()],
+        ast::TopDecl::Table(inner) => inner.fields().iter().map(|field| field.typ()).collect(),
+    }
+}
+
+fn fixed_size(decl: &ast::TopDecl) -> Option<usize> {
+    match decl {
+        ast::TopDecl::Primitive(inner) => Some(inner.size()),
+        ast::TopDecl::Array(inner) => Some(inner.total_size()),
+        ast::TopDecl::Struct(inner) => Some(inner.total_size()),
+        _ => None,
+    }
+}
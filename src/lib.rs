@@ -1,22 +1,184 @@
+//! A [molecule](https://github.com/nervosnetwork/molecule) plugin which generates CellScript code.
+//!
+//! Besides the `moleculec-cellscript` binary, the generator can be embedded into other Rust tools,
+//! such as build scripts and test harnesses. The library functions never parse the process
+//! arguments, read stdin or exit the process.
+//!
+//! ```no_run
+//! use moleculec_cellscript::{generate_to_string, parse_schema_file, Options};
+//!
+//! let ast = parse_schema_file("blockchain.mol".as_ref())?;
+//! let mut options = Options::default();
+//! options.types = vec!["WitnessArgs".to_owned()];
+//! let code = generate_to_string(&ast, &options)?;
+//! # Ok::<(), moleculec_cellscript::Error>(())
+//! ```
+
 use std::{
-    io::{self, Read, Write},
-    process,
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
 };
 
-use molecule_codegen::IntermediateFormat;
+pub use molecule_codegen::{ast, IntermediateFormat};
 
 mod codegen;
+mod diff;
+mod error;
+mod extension;
+mod fingerprint;
+mod ident;
+mod input;
+pub mod layout;
+mod merge;
+mod options;
+mod output;
+mod stats;
 
 use codegen::Generator;
+pub use codegen::PLUGIN_VERSION;
+pub use error::{
+    Error, EXIT_CONFIG, EXIT_GENERATE, EXIT_READ, EXIT_RECOVER, EXIT_STALE, EXIT_WRITE,
+};
+pub use extension::Extension;
+pub use ident::Rename;
+use input::InputFormat;
+pub use options::{Components, Header, NamingStyle, Options, CONFIG_FILE_NAME};
+pub use stats::{Counts, DeclStats, Stats};
+
+/// Generates the CellScript code for the schema and writes it into `writer`.
+///
+/// The options are checked against the schema before anything is written. Failures of `writer`
+/// are reported as [`Error::Generate`].
+pub fn generate<W: io::Write>(
+    writer: &mut W,
+    ast: &ast::Ast,
+    options: &Options,
+) -> Result<(), Error> {
+    Generator::generate(writer, ast, options).map(|_| ())
+}
+
+/// Generates the CellScript code for the schema.
+pub fn generate_to_string(ast: &ast::Ast, options: &Options) -> Result<String, Error> {
+    let mut output_data = Vec::new();
+    Generator::generate(&mut output_data, ast, options)?;
+    Ok(String::from_utf8(output_data).expect("the generated code is UTF-8"))
+}
+
+/// Generates the CellScript code for the schema as one file per declaration, plus a file for the
+/// shared prelude.
+///
+/// Returns the file names and their contents, the files are not written anywhere.
+pub fn generate_split(ast: &ast::Ast, options: &Options) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    Generator::generate_split(ast, options, &mut Stats::default())
+}
+
+/// Counts the functions and the lines which are generated for each declaration of the schema.
+///
+/// The code is generated as by [`generate`] and thrown away. The counts are the same for the split
+/// output, which holds the same declarations.
+pub fn generate_stats(ast: &ast::Ast, options: &Options) -> Result<Stats, Error> {
+    Generator::generate(&mut io::sink(), ast, options)
+}
+
+/// Lists the names of the schema which are escaped in the generated code.
+///
+/// A type or field name which is a keyword or a predeclared identifier of CellScript is generated
+/// with a trailing underscore, e.g. a field `type` becomes the builder field `type_`. Only the
+/// declarations which [`generate`] would generate are listed.
+pub fn escaped_names(ast: &ast::Ast, options: &Options) -> Result<Vec<Rename>, Error> {
+    Generator::renames(ast, options)
+}
+
+/// Generates the molecule runtime as a package of its own.
+///
+/// By default, every generated package inlines the runtime as its prelude. When
+/// [`Options::runtime`] is set, the generated packages import the runtime from that path instead,
+/// and this function generates the package to put there. It's named after the last segment of the
+/// path.
+pub fn generate_runtime<W: io::Write>(writer: &mut W, options: &Options) -> Result<(), Error> {
+    Generator::generate_runtime(writer, options)
+}
+
+/// Hashes the shape of the schema into `sha256:<hex>`.
+///
+/// The fingerprint is written into the header of the generated code when
+/// [`Header::fingerprint`] is set, so tools can tell whether a generated file still matches its
+/// schema without generating the code again. It doesn't depend on the comments and the formatting
+/// of the schema, nor on the versions of molecule and the plugin.
+pub fn schema_fingerprint(ast: &ast::Ast) -> String {
+    fingerprint::schema_fingerprint(ast)
+}
+
+/// Compiles a schema file into the AST which the generator consumes.
+///
+/// molecule reports invalid schemas by panicking. The panic is caught and returned as
+/// [`Error::Recover`]. The first call wraps the panic hook of the process, so that it stays quiet
+/// about the panics which are caught; the panics of other threads are still reported.
+pub fn parse_schema_file(schema_file: &Path) -> Result<ast::Ast, Error> {
+    input::parse_schema_file(schema_file)
+}
+
+/// Finds the namespace of every declaration which the schema file imports from other schemas.
+///
+/// The AST doesn't record which imported schema a declaration comes from, so the imported files
+/// are compiled as well. Set the result as [`Options::imported_namespaces`], so the generated code
+/// refers to the imported types through the packages of their schemas.
+pub fn imported_namespaces(
+    schema_file: &Path,
+    ast: &ast::Ast,
+) -> Result<BTreeMap<String, String>, Error> {
+    input::imported_namespaces(schema_file, ast)
+}
+
+/// Merges several schemas into one, so they're generated into a single package.
+///
+/// Each schema comes with a name for the messages, such as its file name, and the merged schema
+/// takes the namespace of the first one. Declarations which arrive with several schemas, e.g.
+/// through a shared import, are kept once. A name which is declared with different shapes is
+/// reported as [`Error::Recover`], naming both sources.
+pub fn merge_schemas(schemas: &[(String, ast::Ast)]) -> Result<ast::Ast, Error> {
+    merge::merge_schemas(schemas)
+}
+
+/// Recovers the AST from the intermediate data of `moleculec`, in JSON or YAML.
+///
+/// Pass `None` as the format to detect it from the data. Data which doesn't parse, and data whose
+/// declarations don't resolve, are reported as [`Error::Recover`].
+pub fn recover_intermediate(
+    data: &[u8],
+    format: Option<IntermediateFormat>,
+) -> Result<ast::Ast, Error> {
+    let format = format.map(InputFormat::Fixed).unwrap_or(InputFormat::Auto);
+    input::recover_intermediate(data, format)
+}
 
 pub(crate) enum AppAction {
     DisplayFormat,
-    ProcessIntermediate(Vec<u8>),
+    GenerateRuntime,
+    /// Reads the intermediate data from these files, or from stdin if there are none.
+    ProcessIntermediate(Vec<PathBuf>),
+    ProcessSchemaFile(Vec<PathBuf>),
 }
 
 pub struct AppConfig {
     action: AppAction,
     format: IntermediateFormat,
+    input_format: InputFormat,
+    output: Option<PathBuf>,
+    check: Option<PathBuf>,
+    config: Option<PathBuf>,
+    package: Option<String>,
+    types: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    template_dir: Option<PathBuf>,
+    header: Option<Vec<String>>,
+    target: Option<String>,
+    runtime: Option<String>,
+    alias_identical: bool,
+    stats: Option<String>,
+    split: Option<PathBuf>,
 }
 
 type RawAppConfig = (IntermediateFormat, clap::ArgMatches);
@@ -26,12 +188,225 @@ pub fn build_commandline(format: IntermediateFormat) -> AppConfig {
         .name("Moleculec Plugin")
         .about("molecule plugin for cellscript")
         .version(clap::crate_version!())
+        .after_help(
+            "Exit codes:\n  \
+             0   success\n  \
+             1   the file passed to --check is stale\n  \
+             2   invalid command line arguments\n  \
+             65  malformed intermediate data or schema\n  \
+             66  failed to read the input\n  \
+             70  failed to generate the code\n  \
+             74  failed to write the output\n  \
+             78  invalid configuration",
+        )
         .arg(
             clap::Arg::new("format")
                 .long("format")
                 .help("Output the supported format for the intermediate data.")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            clap::Arg::new("schema-file")
+                .long("schema-file")
+                .value_name("SCHEMA_FILE")
+                .help(
+                    "Compile the schema file directly instead of reading the intermediate data \
+                     from stdin. Given several times, the schemas are merged into one package.",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .action(clap::ArgAction::Append)
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("input")
+                .long("input")
+                .value_name("INPUT_FILE")
+                .help(
+                    "Read the intermediate data from the file instead of stdin. \
+                     Given several times, the schemas are merged into one package.",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .action(clap::ArgAction::Append)
+                .conflicts_with_all(["format", "schema-file"]),
+        )
+        .arg(
+            clap::Arg::new("input-format")
+                .long("input-format")
+                .value_name("INPUT_FORMAT")
+                .help(
+                    "The format of the intermediate data. \
+                     \"auto\" detects JSON or YAML from the data itself.",
+                )
+                .value_parser(InputFormat::POSSIBLE_VALUES)
+                .default_value("auto")
+                .conflicts_with("schema-file"),
+        )
+        .arg(
+            clap::Arg::new("output")
+                .long("output")
+                .value_name("OUTPUT_FILE")
+                .help(
+                    "Write the generated code into the file instead of stdout. \
+                     The file is only replaced when the generation succeeds.",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("split")
+                .long("split")
+                .value_name("OUTPUT_DIR")
+                .help(
+                    "Write the prelude and each declaration into its own file in the directory. \
+                     Generated files which no longer correspond to a declaration are removed.",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["format", "output"]),
+        )
+        .arg(
+            clap::Arg::new("check")
+                .long("check")
+                .value_name("EXISTING_FILE")
+                .help(
+                    "Generate the code in memory and compare it with the existing file. \
+                     A unified diff is printed and the exit code is 1 if they differ.",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["format", "output", "split"]),
+        )
+        .arg(
+            clap::Arg::new("config")
+                .long("config")
+                .value_name("CONFIG_FILE")
+                .help(
+                    "The configuration file of the generator. By default, \
+                     moleculec-cellscript.toml is looked up next to the schema file, \
+                     or in the current directory when the schema file is unknown.",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("package")
+                .long("package")
+                .value_name("PACKAGE")
+                .help(
+                    "The package name of the generated code. \
+                     Defaults to the namespace of the schema, which is the schema file name.",
+                )
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("types")
+                .long("types")
+                .value_name("TYPES")
+                .help(
+                    "Only generate these declarations, separated by commas, \
+                     and the declarations they depend on.",
+                )
+                .value_delimiter(',')
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("exclude")
+                .long("exclude")
+                .value_name("TYPES")
+                .help(
+                    "Don't generate these declarations, separated by commas. \
+                     It's an error if another generated declaration depends on them.",
+                )
+                .value_delimiter(',')
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("header")
+                .long("header")
+                .value_name("LINES")
+                .help(
+                    "The lines of the header comment, separated by commas: \
+                     \"versions\" of molecule and the plugin, the \"fingerprint\" of the schema \
+                     and the \"schema\" name. Defaults to \"versions\".",
+                )
+                .value_parser(options::Header::POSSIBLE_VALUES)
+                .value_delimiter(',')
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("target")
+                .long("target")
+                .value_name("VERSION")
+                .help(
+                    "The version of the CellScript compiler which the generated code targets, \
+                     \"0.1\" by default.",
+                )
+                .value_parser(codegen::dialect::names())
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("template-dir")
+                .long("template-dir")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help(
+                    "A directory of <name>.cell files which override the code templates \
+                     of the same names.",
+                )
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("runtime")
+                .long("runtime")
+                .value_name("IMPORT_PATH")
+                .help(
+                    "Import the molecule runtime from this package instead of inlining it \
+                     into the generated package.",
+                )
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("runtime-only")
+                .long("runtime-only")
+                .help(
+                    "Generate only the molecule runtime, as the package which --runtime imports. \
+                     It's named after the last segment of --runtime, \"molecule\" by default.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "format",
+                    "schema-file",
+                    "input",
+                    "split",
+                    "types",
+                    "exclude",
+                    "package",
+                    "alias-identical",
+                    "stats",
+                ]),
+        )
+        .arg(
+            clap::Arg::new("alias-identical")
+                .long("alias-identical")
+                .help(
+                    "Generate the code of declarations with the same layout once, \
+                     and the others as wrappers of the first one.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("format"),
+        )
+        .arg(
+            clap::Arg::new("stats")
+                .long("stats")
+                .value_name("FORMAT")
+                .help(
+                    "Print the number of functions and lines generated for each declaration \
+                     and component to stderr, as a \"text\" table (the default) or as \"json\". \
+                     The biggest declarations are flagged.",
+                )
+                .value_parser(["text", "json"])
+                .num_args(0..=1)
+                .default_missing_value("text")
+                .conflicts_with("format"),
+        )
         .get_matches();
     AppConfig::from((format, matches))
 }
@@ -41,38 +416,253 @@ impl From<RawAppConfig> for AppConfig {
         let (format, matches) = input;
         let action = if matches.get_flag("format") {
             AppAction::DisplayFormat
+        } else if matches.get_flag("runtime-only") {
+            AppAction::GenerateRuntime
+        } else if let Some(schema_files) = matches.get_many::<PathBuf>("schema-file") {
+            AppAction::ProcessSchemaFile(schema_files.cloned().collect())
         } else {
-            let mut input = Vec::new();
-            if io::stdin().read_to_end(&mut input).is_err() {
-                eprintln!("Error: failed to read data from stdin)");
-                process::exit(1);
-            };
-            AppAction::ProcessIntermediate(input)
+            let input_files = matches.get_many::<PathBuf>("input").into_iter().flatten();
+            AppAction::ProcessIntermediate(input_files.cloned().collect())
         };
-        Self { action, format }
+        let input_format = matches
+            .get_one::<String>("input-format")
+            .and_then(|name| InputFormat::from_name(name))
+            .unwrap_or(InputFormat::Auto);
+        let output = matches.get_one::<PathBuf>("output").cloned();
+        let check = matches.get_one::<PathBuf>("check").cloned();
+        let split = matches.get_one::<PathBuf>("split").cloned();
+        let config = matches.get_one::<PathBuf>("config").cloned();
+        let package = matches.get_one::<String>("package").cloned();
+        let types = matches
+            .get_many::<String>("types")
+            .map(|types| types.cloned().collect());
+        let exclude = matches
+            .get_many::<String>("exclude")
+            .map(|exclude| exclude.cloned().collect());
+        let template_dir = matches.get_one::<PathBuf>("template-dir").cloned();
+        let header = matches
+            .get_many::<String>("header")
+            .map(|header| header.cloned().collect());
+        let target = matches.get_one::<String>("target").cloned();
+        let runtime = matches.get_one::<String>("runtime").cloned();
+        let alias_identical = matches.get_flag("alias-identical");
+        let stats = matches.get_one::<String>("stats").cloned();
+        Self {
+            action,
+            format,
+            input_format,
+            output,
+            check,
+            config,
+            package,
+            types,
+            exclude,
+            template_dir,
+            header,
+            target,
+            runtime,
+            alias_identical,
+            stats,
+            split,
+        }
     }
 }
 
 impl AppConfig {
-    pub fn execute(self) {
-        match self.action {
-            AppAction::DisplayFormat => {
-                println!("{}", self.format);
-            }
-            AppAction::ProcessIntermediate(input) => {
-                let ast = self.format.recover(&input).unwrap();
+    pub fn execute(self) -> Result<(), Error> {
+        // moleculec asks for the format before it pipes the data, this mustn't depend on the
+        // configuration.
+        if let AppAction::DisplayFormat = self.action {
+            let format = match self.input_format {
+                InputFormat::Fixed(format) => format,
+                InputFormat::Auto => self.format,
+            };
+            println!("{}", format);
+            return Ok(());
+        }
 
-                let mut output_data = Vec::<u8>::new();
+        let schema_dir = match self.action {
+            AppAction::ProcessSchemaFile(ref schema_files) => schema_files[0].parent(),
+            _ => None,
+        };
+        let mut options = self.load_options(schema_dir.unwrap_or_else(|| Path::new(".")))?;
 
-                if let Err(err) = Generator::generate(&mut output_data, &ast) {
-                    eprintln!("failed to write data by generator: {}", err)
+        let ast = match self.action {
+            AppAction::DisplayFormat => unreachable!("the format is displayed before"),
+            AppAction::GenerateRuntime => {
+                let mut output_data = Vec::<u8>::new();
+                generate_runtime(&mut output_data, &options)?;
+                return write_output(self.check, self.output, &output_data);
+            }
+            AppAction::ProcessIntermediate(input_files) => {
+                let format = match self.input_format {
+                    InputFormat::Fixed(format) => Some(format),
+                    InputFormat::Auto => None,
+                };
+                if input_files.is_empty() {
+                    recover_intermediate(&input::read_input(None)?, format)?
+                } else {
+                    let mut schemas = Vec::with_capacity(input_files.len());
+                    for input_file in input_files {
+                        let input = input::read_input(Some(&input_file))?;
+                        let ast = recover_intermediate(&input, format)?;
+                        schemas.push((input_file.display().to_string(), ast));
+                    }
+                    merge_all(schemas)?
                 }
-
-                let stdout = io::stdout();
-                let mut stdout_handle = stdout.lock();
-                stdout_handle.write_all(&output_data).unwrap();
-                stdout_handle.flush().unwrap();
             }
+            AppAction::ProcessSchemaFile(schema_files) => {
+                let mut schemas = Vec::with_capacity(schema_files.len());
+                for schema_file in schema_files {
+                    let ast = parse_schema_file(&schema_file)?;
+                    if !ast.imports().is_empty() {
+                        let namespaces = imported_namespaces(&schema_file, &ast)?;
+                        options.imported_namespaces.extend(namespaces);
+                    }
+                    schemas.push((schema_file.display().to_string(), ast));
+                }
+                merge_all(schemas)?
+            }
+        };
+
+        for rename in escaped_names(&ast, &options)? {
+            eprintln!("Warning: {}", rename);
+        }
+
+        if let Some(dir) = self.split {
+            let mut stats = Stats::default();
+            let files = Generator::generate_split(&ast, &options, &mut stats)?;
+            report_stats(self.stats.as_deref(), &stats);
+            return output::write_split(&dir, &files, codegen::GENERATED_MARKER).map_err(
+                |source| Error::Write {
+                    path: Some(dir),
+                    source,
+                },
+            );
+        }
+
+        let mut output_data = Vec::<u8>::new();
+        let stats = Generator::generate(&mut output_data, &ast, &options)?;
+        report_stats(self.stats.as_deref(), &stats);
+        write_output(self.check, self.output, &output_data)
+    }
+
+    fn load_options(&self, search_dir: &Path) -> Result<Options, Error> {
+        let config = self
+            .config
+            .clone()
+            .or_else(|| Options::discover(search_dir));
+        let mut options = match config {
+            Some(config) => Options::load(&config)?,
+            None => Options::default(),
+        };
+        if let Some(ref package) = self.package {
+            options.package = Some(package.clone());
+        }
+        if let Some(ref types) = self.types {
+            options.types = types.clone();
+        }
+        if let Some(ref exclude) = self.exclude {
+            options.exclude = exclude.clone();
+        }
+        if let Some(ref template_dir) = self.template_dir {
+            options.template_dir = Some(template_dir.clone());
+        }
+        if let Some(ref target) = self.target {
+            options.target = Some(target.clone());
+        }
+        if let Some(ref runtime) = self.runtime {
+            options.runtime = Some(runtime.clone());
+        }
+        if self.alias_identical {
+            options.alias_identical = true;
+        }
+        if let Some(ref header) = self.header {
+            options.header = options::Header::from_names(header);
+        }
+        if let AppAction::ProcessSchemaFile(ref schema_files) = self.action {
+            let names = schema_files
+                .iter()
+                .filter_map(|schema_file| schema_file.file_name())
+                .map(|name| name.to_string_lossy())
+                .collect::<Vec<_>>();
+            options.schema_name = Some(names.join(", "));
+        }
+        Ok(options)
+    }
+}
+
+/// Merges the schemas of several inputs, a single one is used as it is.
+fn merge_all(mut schemas: Vec<(String, ast::Ast)>) -> Result<ast::Ast, Error> {
+    if schemas.len() == 1 {
+        Ok(schemas.remove(0).1)
+    } else {
+        merge_schemas(&schemas)
+    }
+}
+
+/// Prints the stats in the `format` asked for with `--stats` on stderr.
+fn report_stats(format: Option<&str>, stats: &Stats) {
+    match format {
+        Some("json") => eprintln!("{}", stats.to_json()),
+        Some(_) => eprint!("{}", stats),
+        None => {}
+    }
+}
+
+/// Writes the generated code into the output file or stdout, or checks the existing file against it.
+fn write_output(
+    check: Option<PathBuf>,
+    output: Option<PathBuf>,
+    output_data: &[u8],
+) -> Result<(), Error> {
+    if let Some(existing) = check {
+        check_file(existing, output_data)
+    } else if let Some(output) = output {
+        output::write_atomically(&output, output_data).map_err(|source| Error::Write {
+            path: Some(output),
+            source,
+        })
+    } else {
+        let stdout = io::stdout();
+        let mut stdout_handle = stdout.lock();
+        stdout_handle
+            .write_all(output_data)
+            .and_then(|_| stdout_handle.flush())
+            .map_err(|source| Error::Write { path: None, source })
+    }
+}
+
+/// Compares the existing file with the freshly generated code, printing a unified diff of the drift.
+fn check_file(existing: PathBuf, output_data: &[u8]) -> Result<(), Error> {
+    let existing_data = fs::read(&existing).map_err(|source| Error::Read {
+        path: Some(existing.clone()),
+        source,
+    })?;
+    let name = existing.display().to_string();
+    let diff = diff::unified_diff(
+        &String::from_utf8_lossy(&existing_data),
+        &String::from_utf8_lossy(output_data),
+        &name,
+        &format!("{} (generated)", name),
+    );
+    match diff {
+        None if existing_data == output_data => Ok(()),
+        None => {
+            println!(
+                "{} differs from the generated code in line endings only",
+                name
+            );
+            Err(Error::Stale(existing))
+        }
+        Some(diff) => {
+            let stdout = io::stdout();
+            let mut stdout_handle = stdout.lock();
+            stdout_handle
+                .write_all(diff.as_bytes())
+                .and_then(|_| stdout_handle.flush())
+                .map_err(|source| Error::Write { path: None, source })?;
+            Err(Error::Stale(existing))
         }
     }
 }
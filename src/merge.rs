@@ -0,0 +1,322 @@
+//! Merges several schemas into one, so they're generated into a single package.
+
+use std::collections::{HashMap, HashSet};
+
+use molecule_codegen::{
+    ast::{self, HasName},
+    IntermediateFormat,
+};
+use serde_json::{json, Value};
+
+use crate::{fingerprint::describe_decl, Error};
+
+/// A declaration of the merged schema and the schema it's found in with the least imported depth.
+struct Merged<'a> {
+    decl: &'a ast::TopDecl,
+    source: &'a str,
+    ast: &'a ast::Ast,
+    imported_depth: usize,
+}
+
+impl Merged<'_> {
+    /// The schema which declares the declaration, for the messages.
+    fn origin(&self) -> String {
+        if self.imported_depth == 0 {
+            return self.source.to_owned();
+        }
+        // molecule doesn't record which import a declaration comes from, it's only known when
+        // there's a single one.
+        match self.ast.imports() {
+            [import] if self.imported_depth == 1 => format!(
+                "the schema \"{}\" imported by {}",
+                import.name(),
+                self.source
+            ),
+            _ => format!("a schema imported by {}", self.source),
+        }
+    }
+}
+
+/// Merges the declarations of the schemas into one schema, which takes the namespace of the first.
+///
+/// Each schema comes with the name of its source for the messages, e.g. its file name. A
+/// declaration which arrives with several schemas, typically through a shared import, is kept once
+/// if it has the same shape everywhere, and a name which is declared with different shapes is an
+/// error. A declaration is generated if any of the schemas declares it itself, and the imports of
+/// one merged schema by another are dropped.
+pub(crate) fn merge_schemas(schemas: &[(String, ast::Ast)]) -> Result<ast::Ast, Error> {
+    let mut merged: Vec<Merged> = Vec::new();
+    let mut index = HashMap::new();
+    for (source, ast) in schemas {
+        for decl in ast.decls() {
+            let imported_depth = imported_depth(decl);
+            match index.get(decl.name()) {
+                Some(&i) => {
+                    let existing: &mut Merged = &mut merged[i];
+                    let found = Merged {
+                        decl,
+                        source,
+                        ast,
+                        imported_depth,
+                    };
+                    if describe_decl(existing.decl) != describe_decl(decl) {
+                        return Err(Error::Recover(format!(
+                            "\"{}\" is declared as `{}` in {} and as `{}` in {}",
+                            decl.name(),
+                            describe_decl(existing.decl),
+                            existing.origin(),
+                            describe_decl(decl),
+                            found.origin()
+                        )));
+                    }
+                    if imported_depth < existing.imported_depth {
+                        *existing = found;
+                    }
+                }
+                None => {
+                    index.insert(decl.name(), merged.len());
+                    merged.push(Merged {
+                        decl,
+                        source,
+                        ast,
+                        imported_depth,
+                    });
+                }
+            }
+        }
+    }
+
+    let namespaces = schemas
+        .iter()
+        .map(|(_, ast)| ast.namespace())
+        .collect::<HashSet<_>>();
+    let mut imports = Vec::new();
+    for (_, ast) in schemas {
+        for import in ast.imports() {
+            let import = json!({
+                "name": import.name(),
+                "paths": import.paths(),
+                "path_supers": import.path_supers(),
+            });
+            if !namespaces.contains(import["name"].as_str().unwrap_or_default())
+                && !imports.contains(&import)
+            {
+                imports.push(import);
+            }
+        }
+    }
+
+    let intermediate = json!({
+        "namespace": schemas.first().map_or("", |(_, ast)| ast.namespace()),
+        "imports": imports,
+        "declarations": merged.iter().map(to_intermediate).collect::<Vec<_>>(),
+    });
+    IntermediateFormat::JSON
+        .recover(intermediate.to_string().as_bytes())
+        .map_err(Error::Recover)
+}
+
+fn imported_depth(decl: &ast::TopDecl) -> usize {
+    match decl {
+        ast::TopDecl::Primitive(_) => 0,
+        ast::TopDecl::Option_(inner) => inner.imported_depth(),
+        ast::TopDecl::Union(inner) => inner.imported_depth(),
+        ast::TopDecl::Array(inner) => inner.imported_depth(),
+        ast::TopDecl::Struct(inner) => inner.imported_depth(),
+        ast::TopDecl::FixVec(inner) => inner.imported_depth(),
+        ast::TopDecl::DynVec(inner) => inner.imported_depth(),
+        ast::TopDecl::Table(inner) => inner.imported_depth(),
+    }
+}
+
+/// The declaration in the intermediate format of molecule, which is how the merged schema is built.
+fn to_intermediate(merged: &Merged) -> Value {
+    let fields = |fields: &[ast::FieldDecl]| {
+        fields
+            .iter()
+            .map(|field| json!({ "name": field.name(), "type": field.typ().name() }))
+            .collect::<Vec<_>>()
+    };
+    let mut value = match merged.decl {
+        ast::TopDecl::Primitive(_) => unreachable!("the primitive types are not declared"),
+        ast::TopDecl::Option_(inner) => json!({
+            "type": "option",
+            "name": inner.name(),
+            "item": inner.item().typ().name(),
+        }),
+        ast::TopDecl::Union(inner) => json!({
+            "type": "union",
+            "name": inner.name(),
+            "items": inner
+                .items()
+                .iter()
+                .map(|item| json!({ "typ": item.typ().name(), "id": item.id() }))
+                .collect::<Vec<_>>(),
+        }),
+        ast::TopDecl::Array(inner) => json!({
+            "type": "array",
+            "name": inner.name(),
+            "item": inner.item().typ().name(),
+            "item_count": inner.item_count(),
+        }),
+        ast::TopDecl::Struct(inner) => json!({
+            "type": "struct",
+            "name": inner.name(),
+            "fields": fields(inner.fields()),
+        }),
+        ast::TopDecl::FixVec(inner) => json!({
+            "type": "fixvec",
+            "name": inner.name(),
+            "item": inner.item().typ().name(),
+        }),
+        ast::TopDecl::DynVec(inner) => json!({
+            "type": "dynvec",
+            "name": inner.name(),
+            "item": inner.item().typ().name(),
+        }),
+        ast::TopDecl::Table(inner) => json!({
+            "type": "table",
+            "name": inner.name(),
+            "fields": fields(inner.fields()),
+        }),
+    };
+    value["imported_depth"] = json!(merged.imported_depth);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTE32: &str = r#"{"type": "array", "name": "Byte32", "item": "byte", "item_count": 32, "imported_depth": 0}"#;
+
+    /// A schema from the intermediate `declarations`, which imports the namespaces `imports`.
+    fn schema(
+        source: &str,
+        namespace: &str,
+        imports: &[&str],
+        declarations: &[&str],
+    ) -> (String, ast::Ast) {
+        let imports = imports
+            .iter()
+            .map(|name| format!(r#"{{"name": "{}", "paths": [], "path_supers": 0}}"#, name))
+            .collect::<Vec<_>>();
+        let ir = format!(
+            r#"{{"namespace": "{}", "imports": [{}], "declarations": [{}]}}"#,
+            namespace,
+            imports.join(", "),
+            declarations.join(", ")
+        );
+        let ast = IntermediateFormat::JSON.recover(ir.as_bytes()).unwrap();
+        (source.to_owned(), ast)
+    }
+
+    fn imported(decl: &str) -> String {
+        decl.replace(r#""imported_depth": 0"#, r#""imported_depth": 1"#)
+    }
+
+    fn table(name: &str, field_type: &str) -> String {
+        format!(
+            r#"{{"type": "table", "name": "{}", "fields": [{{"name": "a", "type": "{}"}}],
+            "imported_depth": 0}}"#,
+            name, field_type
+        )
+    }
+
+    fn merge(schemas: &[(String, ast::Ast)]) -> Result<ast::Ast, String> {
+        merge_schemas(schemas).map_err(|err| match err {
+            Error::Recover(message) => message,
+            err => panic!("unexpected error {:?}", err),
+        })
+    }
+
+    #[test]
+    fn shared_declarations_are_merged_once() {
+        let merged = merge(&[
+            schema(
+                "chain.mol",
+                "chain",
+                &["basic"],
+                &[&imported(BYTE32), &table("Script", "Byte32")],
+            ),
+            schema("basic.mol", "basic", &[], &[BYTE32]),
+            schema(
+                "other.mol",
+                "other",
+                &["basic"],
+                &[&imported(BYTE32), &table("Cell", "Byte32")],
+            ),
+        ])
+        .unwrap();
+        assert_eq!(merged.namespace(), "chain");
+        // basic is merged itself, so it isn't imported anymore.
+        assert!(merged.imports().is_empty());
+        let decls = merged
+            .major_decls()
+            .iter()
+            .map(|decl| decl.name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(decls, ["Byte32", "Script", "Cell"]);
+    }
+
+    #[test]
+    fn imports_of_schemas_which_are_not_merged_are_kept_once() {
+        let merged = merge(&[
+            schema(
+                "chain.mol",
+                "chain",
+                &["basic"],
+                &[&imported(BYTE32), &table("Script", "Byte32")],
+            ),
+            schema(
+                "other.mol",
+                "other",
+                &["basic"],
+                &[&imported(BYTE32), &table("Cell", "Byte32")],
+            ),
+        ])
+        .unwrap();
+        let imports = merged
+            .imports()
+            .iter()
+            .map(|import| import.name())
+            .collect::<Vec<_>>();
+        assert_eq!(imports, ["basic"]);
+        assert_eq!(merged.major_decls().len(), 2);
+        assert_eq!(merged.decls().len(), 3);
+    }
+
+    #[test]
+    fn a_conflict_names_the_declaring_schemas() {
+        let byte20 = BYTE32.replace("32,", "20,");
+        assert_eq!(
+            merge(&[
+                schema(
+                    "chain.mol",
+                    "chain",
+                    &["basic"],
+                    &[&imported(BYTE32), &table("Script", "Byte32")]
+                ),
+                schema("hash.mol", "hash", &[], &[&byte20]),
+            ])
+            .unwrap_err(),
+            "\"Byte32\" is declared as `array Byte32 byte 32` in the schema \"basic\" imported by \
+             chain.mol and as `array Byte32 byte 20` in hash.mol"
+        );
+        assert_eq!(
+            merge(&[
+                schema(
+                    "chain.mol",
+                    "chain",
+                    &["basic"],
+                    &[&imported(BYTE32), &table("Script", "Byte32")]
+                ),
+                schema("basic.mol", "basic", &[], &[BYTE32]),
+                schema("hash.mol", "hash", &[], &[&byte20]),
+            ])
+            .unwrap_err(),
+            "\"Byte32\" is declared as `array Byte32 byte 32` in basic.mol and as \
+             `array Byte32 byte 20` in hash.mol"
+        );
+    }
+}
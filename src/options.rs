@@ -0,0 +1,695 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use case::CaseExt;
+use molecule_codegen::ast::{self, HasName};
+use serde::Deserialize;
+
+use crate::{
+    codegen::dialect,
+    extension::{Extension, Extensions},
+    ident::{self, Rename},
+    layout::{DeclLayout, Layout},
+    Error,
+};
+
+/// The name of the runtime package when `runtime` isn't set.
+const DEFAULT_RUNTIME_PACKAGE: &str = "molecule";
+
+/// The name of the configuration file which is discovered next to the schema.
+pub const CONFIG_FILE_NAME: &str = "moleculec-cellscript.toml";
+
+/// Options of the code generator.
+///
+/// They are usually loaded from a `moleculec-cellscript.toml`:
+///
+/// ```toml
+/// package = "chain"
+/// naming = "camel"
+/// type-mappings = { Byte32 = "Hash" }
+///
+/// [header]
+/// versions = false
+/// fingerprint = true
+///
+/// [components]
+/// readers = true
+/// builders = false
+/// verification = true
+/// ```
+///
+/// Embedders start from the defaults and set the fields they need:
+///
+/// ```
+/// let mut options = moleculec_cellscript::Options::default();
+/// options.package = Some("chain".to_owned());
+/// options.components.builders = false;
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct Options {
+    /// The package name of the generated code. Defaults to the namespace of the schema.
+    pub package: Option<String>,
+    /// How the declaration names in the schema are turned into CellScript type names.
+    pub naming: NamingStyle,
+    /// Which components are generated for each declaration.
+    pub components: Components,
+    /// What the header comment of the generated files holds.
+    pub header: Header,
+    /// The CellScript dialect of the generated code, see `--target`. Defaults to "0.1".
+    pub target: Option<String>,
+    /// Renames declarations of the schema in the generated code.
+    pub type_mappings: BTreeMap<String, String>,
+    /// Only generate these declarations and their dependencies. Empty means all declarations.
+    pub types: Vec<String>,
+    /// Don't generate these declarations.
+    pub exclude: Vec<String>,
+    /// Generate the code of declarations with the same layout once. The first of them gets the full
+    /// code, and the others wrap it and convert from and to it.
+    pub alias_identical: bool,
+    /// The import path of the runtime package, which `--runtime-only` generates. When it's set, the
+    /// generated code imports the runtime instead of inlining it as a prelude.
+    pub runtime: Option<String>,
+    /// The CellScript import paths of the imported schemas, by molecule namespace. A namespace
+    /// which isn't listed is imported by its name. The package is referred to by the last segment
+    /// of the path.
+    pub imports: BTreeMap<String, String>,
+    /// A directory of `<name>.cell` files which override the embedded code templates. A relative
+    /// path in the configuration file is relative to the file.
+    pub template_dir: Option<PathBuf>,
+    /// The schema name in the header. The binary sets it to the file name of `--schema-file`, and
+    /// it defaults to the namespace of the schema.
+    #[serde(skip)]
+    pub schema_name: Option<String>,
+    /// The namespace of each declaration which comes from an imported schema. The binary finds
+    /// them with [`imported_namespaces`](crate::imported_namespaces) from the imported files of
+    /// `--schema-file`. Without them, only the declarations of a single imported schema can be
+    /// attributed.
+    #[serde(skip)]
+    pub imported_namespaces: BTreeMap<String, String>,
+    /// The hooks which append code to the declarations, registered with `add_extension`.
+    #[serde(skip)]
+    extensions: Extensions,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamingStyle {
+    /// `code_hash` becomes `CodeHash`.
+    #[default]
+    Camel,
+    /// Names are used as they are written in the schema.
+    Preserve,
+}
+
+/// The parts of the generated code which can be turned off.
+///
+/// The type definition, `FromSliceUnchecked`, `AsSlice` and `Default` are always generated.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Components {
+    /// Getters such as `Nth0`, `Get`, `Len` and the field accessors.
+    pub readers: bool,
+    /// The `Builder` types. `AsBuilder` also requires the readers.
+    pub builders: bool,
+    /// The `FromSlice` constructors, which verify the data.
+    pub verification: bool,
+}
+
+impl Default for Components {
+    fn default() -> Self {
+        Self {
+            readers: true,
+            builders: true,
+            verification: true,
+        }
+    }
+}
+
+/// The lines of the header comment of the generated files.
+///
+/// The `// Generated by Moleculec-CellScript` line is always written, it marks the generated files.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Header {
+    /// The versions of molecule and of the plugin. Without them, upgrading the plugin only changes
+    /// the files whose code changes.
+    pub versions: bool,
+    /// The fingerprint of the schema, see [`schema_fingerprint`](crate::schema_fingerprint).
+    pub fingerprint: bool,
+    /// The name of the schema which the code is generated from.
+    pub schema: bool,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            versions: true,
+            fingerprint: false,
+            schema: false,
+        }
+    }
+}
+
+impl Header {
+    /// The names of the lines for `--header`.
+    pub(crate) const POSSIBLE_VALUES: [&'static str; 3] = ["versions", "fingerprint", "schema"];
+
+    /// The header which holds exactly the named lines.
+    pub(crate) fn from_names<S: AsRef<str>>(names: &[S]) -> Self {
+        let has = |line: &str| names.iter().any(|name| name.as_ref() == line);
+        Self {
+            versions: has("versions"),
+            fingerprint: has("fingerprint"),
+            schema: has("schema"),
+        }
+    }
+}
+
+impl NamingStyle {
+    fn apply(self, name: &str) -> String {
+        match self {
+            Self::Camel => name.to_camel(),
+            Self::Preserve => name.to_owned(),
+        }
+    }
+}
+
+impl Options {
+    /// Loads the options from the configuration file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|source| Error::Read {
+            path: Some(path.to_owned()),
+            source,
+        })?;
+        let mut options: Self = toml::from_str(&content)
+            .map_err(|err| Error::Config(format!("{}: {}", path.display(), err)))?;
+        if let (Some(dir), Some(parent)) = (&mut options.template_dir, path.parent()) {
+            *dir = parent.join(&dir);
+        }
+        Ok(options)
+    }
+
+    /// Finds the configuration file in `dir`.
+    pub fn discover(dir: &Path) -> Option<PathBuf> {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Registers a hook which appends code to the generated declarations.
+    ///
+    /// The extensions are called in the order they're registered.
+    pub fn add_extension<E: Extension + 'static>(&mut self, extension: E) -> &mut Self {
+        self.extensions.push(extension);
+        self
+    }
+
+    pub(crate) fn extensions(&self) -> impl Iterator<Item = &dyn Extension> {
+        self.extensions.iter()
+    }
+
+    /// The package name of the generated code.
+    pub(crate) fn package_name<'a>(&'a self, ast: &'a ast::Ast) -> &'a str {
+        self.package.as_deref().unwrap_or_else(|| ast.namespace())
+    }
+
+    /// The schema name in the header.
+    pub(crate) fn schema_name<'a>(&'a self, ast: &'a ast::Ast) -> &'a str {
+        self.schema_name
+            .as_deref()
+            .unwrap_or_else(|| ast.namespace())
+    }
+
+    /// Checks the options against the schema and attributes the imported declarations which
+    /// `imported_namespaces` misses.
+    pub(crate) fn resolve(&self, ast: &ast::Ast, layout: &Layout) -> Result<Self, Error> {
+        self.validate(ast)?;
+        let mut options = self.clone();
+        // A declaration of the schema itself may come from an imported file as well, when several
+        // schemas are merged.
+        options.imported_namespaces.retain(|name, _| {
+            layout
+                .get(name)
+                .is_some_and(|layout| layout.imported_depth > 0)
+        });
+        let only_import = match ast.imports() {
+            [import] => Some(import.name()),
+            _ => None,
+        };
+        for decl in ast.decls() {
+            let depth = layout
+                .get(decl.name())
+                .map_or(0, |layout| layout.imported_depth);
+            if depth == 0 || options.imported_namespaces.contains_key(decl.name()) {
+                continue;
+            }
+            match only_import {
+                Some(namespace) if depth == 1 => {
+                    options
+                        .imported_namespaces
+                        .insert(decl.name().to_owned(), namespace.to_owned());
+                }
+                _ => {
+                    return Err(Error::Config(format!(
+                        "can't tell which imported schema declares \"{}\", \
+                         compile the schema with --schema-file",
+                        decl.name()
+                    )))
+                }
+            }
+        }
+
+        // The configuration is shared by the schemas of a directory, so it may list the
+        // namespaces of other schemas.
+        let namespaces = options
+            .imported_namespaces
+            .values()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        options
+            .imports
+            .retain(|namespace, _| namespaces.contains(namespace));
+        for namespace in &namespaces {
+            let package = options.import_package(namespace);
+            if !ident::is_identifier(package) || package == options.package_name(ast) {
+                return Err(Error::Config(format!(
+                    "the package \"{}\" of the imported schema \"{}\" can't be referred to \
+                     from the package \"{}\", map the namespace to another import path in \
+                     `imports`",
+                    package,
+                    namespace,
+                    options.package_name(ast)
+                )));
+            }
+        }
+        Ok(options)
+    }
+
+    /// The name which the generated code refers to the runtime package by, the last segment of
+    /// `runtime`.
+    pub(crate) fn runtime_package(&self) -> &str {
+        let path = self.runtime.as_deref().unwrap_or(DEFAULT_RUNTIME_PACKAGE);
+        path.rsplit('/').next().unwrap_or(path)
+    }
+
+    /// Checks the options for generating the runtime package.
+    pub(crate) fn validate_runtime(&self) -> Result<(), Error> {
+        self.validate_target()?;
+        let package = self.runtime_package();
+        if !ident::is_identifier(package) {
+            return Err(Error::Config(format!(
+                "the runtime package name \"{}\" is not a valid CellScript identifier, \
+                 it's the last segment of `runtime`",
+                package
+            )));
+        }
+        Ok(())
+    }
+
+    /// The import path of the imported schema `namespace`.
+    pub(crate) fn import_path<'a>(&'a self, namespace: &'a str) -> &'a str {
+        self.imports
+            .get(namespace)
+            .map_or(namespace, String::as_str)
+    }
+
+    /// The name which the generated code refers to the imported schema `namespace` by.
+    pub(crate) fn import_package<'a>(&'a self, namespace: &'a str) -> &'a str {
+        let path = self.import_path(namespace);
+        path.rsplit('/').next().unwrap_or(path)
+    }
+
+    /// The import paths and the package names of the imported schemas.
+    pub(crate) fn schema_imports(&self) -> Vec<(&str, &str)> {
+        let namespaces = self
+            .imported_namespaces
+            .values()
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+        namespaces
+            .into_iter()
+            .map(|namespace| (self.import_package(namespace), self.import_path(namespace)))
+            .collect()
+    }
+
+    /// Checks the options against the schema.
+    fn validate(&self, ast: &ast::Ast) -> Result<(), Error> {
+        let package = self.package_name(ast);
+        if !ident::is_identifier(package) {
+            let hint = if self.package.is_some() {
+                ""
+            } else {
+                ", it's taken from the schema namespace, \
+                 set another one with --package or `package` in the configuration file"
+            };
+            return Err(Error::Config(format!(
+                "the package name \"{}\" is not a valid CellScript identifier{}",
+                package, hint
+            )));
+        }
+        self.validate_target()?;
+        if self.runtime.is_some() {
+            self.validate_runtime()?;
+            if self.runtime_package() == package {
+                return Err(Error::Config(format!(
+                    "the runtime package and the generated package are both named \"{}\"",
+                    package
+                )));
+            }
+        }
+        let mut mapped_names = BTreeMap::new();
+        for (name, mapped) in &self.type_mappings {
+            if name == "byte" {
+                return Err(Error::Config(
+                    "type-mappings: \"byte\" is a primitive type, which can't be renamed"
+                        .to_owned(),
+                ));
+            }
+            // The configuration is shared by the schemas of a directory, so it may rename the
+            // declarations of other schemas.
+            if !ast.decls().iter().any(|decl| decl.name() == name) {
+                continue;
+            }
+            if !ident::is_identifier(mapped) {
+                return Err(Error::Config(format!(
+                    "type-mappings: \"{}\" is mapped to \"{}\", which is not a valid CellScript \
+                     identifier",
+                    name, mapped
+                )));
+            }
+            if let Some(other) = mapped_names.insert(mapped, name) {
+                return Err(Error::Config(format!(
+                    "type-mappings: \"{}\" and \"{}\" are both mapped to \"{}\"",
+                    other, name, mapped
+                )));
+            }
+        }
+        // The declarations of the imported schemas live in their own packages.
+        let major_decls = ast.major_decls();
+        let local_names = major_decls
+            .iter()
+            .map(|decl| decl.name())
+            .chain(Some("byte"));
+        for other in local_names {
+            if self.type_mappings.contains_key(other) {
+                continue;
+            }
+            let generated = self.unescaped_type_name(other);
+            if let Some(name) = mapped_names.get(&generated) {
+                return Err(Error::Config(format!(
+                    "type-mappings: \"{}\" is mapped to \"{}\", which is the name of \"{}\"",
+                    name, generated, other
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_target(&self) -> Result<(), Error> {
+        if let Some(ref target) = self.target {
+            if dialect::find(target).is_none() {
+                return Err(Error::Config(format!(
+                    "unknown target \"{}\", the targets are: {}",
+                    target,
+                    dialect::names().join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of a declaration in the generated code, qualified by the package if it's imported.
+    /// The primitive type is qualified by the runtime package when `runtime` is set.
+    pub(crate) fn type_name(&self, name: &str) -> String {
+        match self.imported_namespaces.get(name) {
+            Some(namespace) => format!(
+                "{}.{}",
+                self.import_package(namespace),
+                self.bare_type_name(name)
+            ),
+            None if name == "byte" && self.runtime.is_some() => {
+                format!("{}.{}", self.runtime_package(), self.bare_type_name(name))
+            }
+            None => self.bare_type_name(name),
+        }
+    }
+
+    /// The name of a declaration in the generated code, without the package, for the names which
+    /// are derived from it.
+    pub(crate) fn bare_type_name(&self, name: &str) -> String {
+        ident::escape(self.unescaped_type_name(name))
+    }
+
+    fn unescaped_type_name(&self, name: &str) -> String {
+        // The primitive type is provided by the prelude under a fixed name.
+        if name == "byte" {
+            return name.to_camel();
+        }
+        match self.type_mappings.get(name) {
+            Some(mapped) => mapped.to_owned(),
+            None => self.naming.apply(name),
+        }
+    }
+
+    /// The name of a method which is derived from a field of the schema.
+    ///
+    /// Methods are always exported, and they must not clash with the builder fields, which keep the
+    /// names written in the schema. Both are escaped when they're reserved, see [`ident`].
+    pub(crate) fn func_name(&self, name: &str) -> String {
+        ident::escape_method(ident::escape(name.to_camel()))
+    }
+
+    /// The name of a builder field, which is the name of the field in the schema.
+    pub(crate) fn field_name(&self, name: &str) -> String {
+        ident::escape(name.to_owned())
+    }
+
+    /// The names of a declaration which are escaped in the generated code.
+    ///
+    /// It's an error if two fields end up with the same builder field or the same methods, e.g.
+    /// when a field is escaped into the name of another one.
+    pub(crate) fn renames(&self, layout: &DeclLayout) -> Result<Vec<Rename>, Error> {
+        let mut renames = Vec::new();
+        renames.extend(Rename::of(
+            &layout.name,
+            None,
+            self.unescaped_type_name(&layout.name),
+        ));
+        let mut field_names = BTreeMap::new();
+        let mut func_names = BTreeMap::new();
+        for field in &layout.fields {
+            renames.extend(Rename::of(
+                &layout.name,
+                Some(&field.name),
+                field.name.clone(),
+            ));
+            renames.extend(Rename::of_method(
+                &layout.name,
+                &field.name,
+                field.name.to_camel(),
+            ));
+
+            let clashes = [
+                (
+                    "builder field",
+                    &mut field_names,
+                    self.field_name(&field.name),
+                ),
+                ("methods", &mut func_names, self.func_name(&field.name)),
+            ];
+            for (what, names, name) in clashes {
+                if let Some(other) = names.insert(name.clone(), &field.name) {
+                    return Err(Error::Config(format!(
+                        "the fields \"{}\" and \"{}\" of \"{}\" both get the {} `{}`",
+                        other, field.name, layout.name, what, name
+                    )));
+                }
+            }
+        }
+        Ok(renames)
+    }
+
+    /// The `AsBuilder` conversions read the data through the getters.
+    pub(crate) fn as_builder(&self) -> bool {
+        self.components.readers && self.components.builders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use molecule_codegen::IntermediateFormat;
+
+    use super::*;
+
+    /// A schema of the namespace `chain`, from its intermediate `imports` and `declarations`.
+    fn schema(imports: &str, declarations: &str) -> ast::Ast {
+        let ir = format!(
+            r#"{{"namespace": "chain", "imports": [{}], "declarations": [{}]}}"#,
+            imports, declarations
+        );
+        IntermediateFormat::JSON.recover(ir.as_bytes()).unwrap()
+    }
+
+    /// A schema with a table `T` of the `fields`, which are all bytes.
+    fn table(fields: &[&str]) -> ast::Ast {
+        let fields = fields
+            .iter()
+            .map(|name| format!(r#"{{"name": "{}", "type": "byte"}}"#, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        schema(
+            "",
+            &format!(
+                r#"{{"type": "table", "name": "T", "fields": [{}], "imported_depth": 0}}"#,
+                fields
+            ),
+        )
+    }
+
+    /// A schema which imports `Byte32` from the schema `basic`.
+    fn importing_schema() -> ast::Ast {
+        schema(
+            r#"{"name": "basic", "paths": [], "path_supers": 0}"#,
+            r#"{"type": "array", "name": "Byte32", "item": "byte", "item_count": 32,
+                "imported_depth": 1},
+            {"type": "table", "name": "Script", "fields": [{"name": "code_hash", "type": "Byte32"}],
+                "imported_depth": 0}"#,
+        )
+    }
+
+    /// The options of a configuration file which is shared by the schemas of a directory.
+    fn shared_config() -> Options {
+        let mut options = Options::default();
+        for (namespace, path) in [
+            ("basic", "example.com/proto/basic"),
+            ("chain", "example.com/proto/chain"),
+        ] {
+            options
+                .imports
+                .insert(namespace.to_owned(), path.to_owned());
+        }
+        options
+    }
+
+    fn resolve(ast: &ast::Ast, options: &Options) -> Result<Options, Error> {
+        options.resolve(ast, &Layout::new(ast))
+    }
+
+    fn renames(fields: &[&str]) -> Result<Vec<String>, Error> {
+        let layout = Layout::new(&table(fields));
+        let renames = Options::default().renames(layout.get("T").unwrap())?;
+        Ok(renames.into_iter().map(|rename| rename.escaped).collect())
+    }
+
+    #[test]
+    fn a_shared_config_may_list_namespaces_which_the_schema_does_not_import() {
+        let options = resolve(&table(&["a"]), &shared_config()).unwrap();
+        assert!(options.imports.is_empty());
+        assert!(options.schema_imports().is_empty());
+
+        let options = resolve(&importing_schema(), &shared_config()).unwrap();
+        assert_eq!(
+            options.schema_imports(),
+            [("basic", "example.com/proto/basic")]
+        );
+    }
+
+    fn validate(type_mappings: &[(&str, &str)]) -> Result<(), Error> {
+        let mut options = Options::default();
+        for (name, mapped) in type_mappings {
+            options
+                .type_mappings
+                .insert((*name).to_owned(), (*mapped).to_owned());
+        }
+        options.validate(&importing_schema())
+    }
+
+    #[test]
+    fn type_mappings_of_other_schemas_are_ignored() {
+        assert!(validate(&[("Script", "Lock"), ("Transaction", "Tx")]).is_ok());
+        assert!(validate(&[("Transaction", "not valid")]).is_ok());
+    }
+
+    #[test]
+    fn type_mappings_must_be_identifiers() {
+        assert!(matches!(
+            validate(&[("Script", "not valid")]),
+            Err(Error::Config(message)) if message.contains("not a valid CellScript identifier")
+        ));
+    }
+
+    #[test]
+    fn type_mappings_must_not_share_a_name() {
+        assert!(matches!(
+            validate(&[("Byte32", "Hash"), ("Script", "Hash")]),
+            Err(Error::Config(message))
+                if message == "type-mappings: \"Byte32\" and \"Script\" are both mapped to \"Hash\""
+        ));
+        assert!(matches!(
+            validate(&[("Byte32", "Script")]),
+            Err(Error::Config(message)) if message.contains("which is the name of \"Script\"")
+        ));
+        assert!(validate(&[("Byte32", "Script"), ("Script", "Lock")]).is_ok());
+    }
+
+    #[test]
+    fn byte_can_not_be_mapped() {
+        assert!(matches!(
+            validate(&[("byte", "Octet")]),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn only_the_known_targets_are_accepted() {
+        let mut options = Options::default();
+        assert!(options.validate_target().is_ok());
+        options.target = Some("0.1".to_owned());
+        assert!(options.validate_target().is_ok());
+        options.target = Some("0.2".to_owned());
+        assert!(matches!(
+            options.validate_target(),
+            Err(Error::Config(message)) if message == "unknown target \"0.2\", the targets are: 0.1"
+        ));
+    }
+
+    #[test]
+    fn keyword_fields_are_escaped_in_the_builder_only() {
+        assert_eq!(renames(&["type", "args"]).unwrap(), ["type_"]);
+        let options = Options::default();
+        assert_eq!(options.field_name("type"), "type_");
+        assert_eq!(options.func_name("type"), "Type");
+    }
+
+    #[test]
+    fn getters_which_clash_with_generated_methods_are_escaped() {
+        assert_eq!(
+            renames(&["len", "total_size", "as_slice"]).unwrap(),
+            ["len_", "Len_", "TotalSize_", "AsSlice_"]
+        );
+        let options = Options::default();
+        assert_eq!(options.func_name("len"), "Len_");
+        assert_eq!(options.func_name("item_id"), "ItemId");
+        assert_eq!(options.func_name("item_count"), "ItemCount_");
+    }
+
+    #[test]
+    fn escaped_names_mustn_t_be_taken_by_another_field() {
+        assert!(matches!(
+            renames(&["len", "len_"]),
+            Err(Error::Config(message)) if message.contains("\"len\" and \"len_\"")
+        ));
+        assert!(matches!(
+            renames(&["type", "Type"]),
+            Err(Error::Config(message)) if message.contains("the methods `Type`")
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    process,
+};
+
+/// Writes `data` into `path` without ever leaving a partially written file behind.
+///
+/// The data is written into a temporary file in the same directory first, which is then renamed
+/// over the target. If anything fails, the existing file is left untouched.
+pub(crate) fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let temp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+
+    let result = write_and_rename(&temp_path, path, data);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_and_rename(temp_path: &Path, path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temp_path, metadata.permissions())?;
+    }
+    fs::rename(temp_path, path)
+}
+
+/// Writes the split output into `dir`, and removes the generated files which are not part of it.
+///
+/// Only files carrying `marker` are considered generated, other files in `dir` are kept.
+pub(crate) fn write_split(dir: &Path, files: &[(String, Vec<u8>)], marker: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (file_name, data) in files {
+        write_atomically(&dir.join(file_name), data)?;
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_stale = path.extension().map(|ext| ext == "cell").unwrap_or(false)
+            && path.is_file()
+            && !files.iter().any(|(file_name, _)| {
+                path.file_name()
+                    .map(|name| name == file_name.as_str())
+                    .unwrap_or(false)
+            })
+            && is_generated(&path, marker)?;
+        if is_stale {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_generated(path: &Path, marker: &str) -> io::Result<bool> {
+    let content = fs::read(path)?;
+    Ok(String::from_utf8_lossy(&content)
+        .lines()
+        .take_while(|line| line.starts_with("//"))
+        .any(|line| line.starts_with(marker)))
+}
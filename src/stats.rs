@@ -0,0 +1,201 @@
+//! How much code is generated for each declaration, to see where the size of the contracts goes.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt, ops::AddAssign};
+
+use serde::Serialize;
+
+/// The most declarations which are flagged as the biggest ones. Only those bigger than the
+/// average are flagged.
+const BIGGEST_COUNT: usize = 5;
+
+/// The functions and the lines of some generated code.
+///
+/// Blank lines don't count, so the numbers don't depend on the layout of the code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Counts {
+    pub functions: usize,
+    pub lines: usize,
+}
+
+impl Counts {
+    pub(crate) fn of(code: &str) -> Self {
+        let mut counts = Self::default();
+        for line in code.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            counts.lines += 1;
+            if line.starts_with("func ") {
+                counts.functions += 1;
+            }
+        }
+        counts
+    }
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, other: Self) {
+        self.functions += other.functions;
+        self.lines += other.lines;
+    }
+}
+
+/// The code generated for one declaration.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct DeclStats {
+    /// The name in the schema.
+    pub name: String,
+    /// The molecule kind, see [`Kind::name`](crate::layout::Kind::name).
+    pub kind: &'static str,
+    #[serde(flatten)]
+    pub total: Counts,
+    /// The code of each component: `common`, `readers`, `builders`, `verification` and
+    /// `extensions`. The components which aren't generated are missing.
+    pub components: BTreeMap<&'static str, Counts>,
+}
+
+/// The code generated for the declarations of a schema, in the order they're generated.
+///
+/// The prelude and the file header aren't counted.
+#[derive(Debug, Clone, Default, Serialize)]
+#[non_exhaustive]
+pub struct Stats {
+    pub decls: Vec<DeclStats>,
+    /// The sums over the declarations, for each component.
+    pub components: BTreeMap<&'static str, Counts>,
+    #[serde(flatten)]
+    pub total: Counts,
+    /// The names of the declarations with the most lines, the biggest first, if they're bigger
+    /// than the average.
+    pub biggest: Vec<String>,
+}
+
+impl Stats {
+    pub(crate) fn push(&mut self, decl: DeclStats) {
+        for (component, counts) in &decl.components {
+            *self.components.entry(component).or_default() += *counts;
+        }
+        self.total += decl.total;
+        self.decls.push(decl);
+    }
+
+    /// Flags the biggest declarations, once all of them are pushed.
+    pub(crate) fn find_biggest(&mut self) {
+        if self.decls.is_empty() {
+            return;
+        }
+        let mut biggest = self.decls.iter().collect::<Vec<_>>();
+        // The sort is stable, so equally big declarations stay in the order of the schema.
+        biggest.sort_by_key(|decl| Reverse(decl.total.lines));
+        let average = self.total.lines / self.decls.len();
+        self.biggest = biggest
+            .into_iter()
+            .take(BIGGEST_COUNT)
+            .filter(|decl| decl.total.lines > average)
+            .map(|decl| decl.name.clone())
+            .collect();
+    }
+
+    /// The report in JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the stats are plain data")
+    }
+}
+
+/// The report as a table, with the biggest declarations marked by `*`.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .decls
+            .iter()
+            // Room for the mark of the biggest declarations.
+            .map(|decl| decl.name.len() + 2)
+            .chain(Some("declaration".len()))
+            .max()
+            .unwrap_or_default();
+        let row = |f: &mut fmt::Formatter, name: &str, kind: &str, counts: &Counts| {
+            writeln!(
+                f,
+                "{:width$}  {:12}  {:>9}  {:>7}",
+                name,
+                kind,
+                counts.functions,
+                counts.lines,
+                width = width
+            )
+        };
+
+        writeln!(
+            f,
+            "{:width$}  {:12}  {:>9}  {:>7}",
+            "declaration",
+            "kind",
+            "functions",
+            "lines",
+            width = width
+        )?;
+        for decl in &self.decls {
+            let name = if self.biggest.contains(&decl.name) {
+                format!("{} *", decl.name)
+            } else {
+                decl.name.clone()
+            };
+            row(f, &name, decl.kind, &decl.total)?;
+        }
+        writeln!(f)?;
+        for (component, counts) in &self.components {
+            row(f, "", component, counts)?;
+        }
+        row(f, "", "total", &self.total)?;
+        if !self.biggest.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "* the biggest declarations: {}", self.biggest.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decl(name: &str, lines: usize) -> DeclStats {
+        let total = Counts {
+            functions: 1,
+            lines,
+        };
+        DeclStats {
+            name: name.to_owned(),
+            kind: "table",
+            total,
+            components: BTreeMap::from([("readers", total)]),
+        }
+    }
+
+    #[test]
+    fn the_biggest_declarations_are_the_ones_above_the_average() {
+        let mut stats = Stats::default();
+        for (i, &lines) in [10, 40, 10, 40, 10, 90, 10].iter().enumerate() {
+            stats.push(decl(&format!("T{}", i), lines));
+        }
+        assert!(stats.biggest.is_empty());
+        stats.find_biggest();
+        assert_eq!(stats.biggest, ["T5", "T1", "T3"]);
+        assert_eq!(
+            stats.total,
+            Counts {
+                functions: 7,
+                lines: 210
+            }
+        );
+        assert_eq!(stats.components["readers"], stats.total);
+    }
+
+    #[test]
+    fn no_declaration_is_the_biggest_of_equal_ones() {
+        let mut stats = Stats::default();
+        stats.find_biggest();
+        stats.push(decl("A", 10));
+        stats.push(decl("B", 10));
+        stats.find_biggest();
+        assert!(stats.biggest.is_empty());
+    }
+}
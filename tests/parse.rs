@@ -0,0 +1,43 @@
+use std::{fs, thread};
+
+use moleculec_cellscript::{parse_schema_file, recover_intermediate, Error};
+
+#[test]
+fn invalid_schemas_are_errors_on_parallel_threads() {
+    let dir =
+        std::env::temp_dir().join(format!("moleculec-cellscript-parse-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let invalid = dir.join("invalid.mol");
+    fs::write(&invalid, "table T { a: Nope, }\n").unwrap();
+
+    let threads = (0..8)
+        .map(|i| {
+            let invalid = invalid.clone();
+            thread::spawn(move || {
+                if i % 2 == 0 {
+                    assert!(matches!(
+                        parse_schema_file(&invalid),
+                        Err(Error::Recover(_))
+                    ));
+                } else {
+                    assert!(parse_schema_file("test/blockchain.mol".as_ref()).is_ok());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unresolved_intermediate_data_is_an_error() {
+    let data =
+        br#"{"namespace": "x", "imports": [], "declarations": [{"type": "table", "name": "T",
+        "fields": [{"name": "a", "type": "Nope"}], "imported_depth": 0}]}"#;
+    assert!(matches!(
+        recover_intermediate(data, None),
+        Err(Error::Recover(_))
+    ));
+}
@@ -0,0 +1,18 @@
+use moleculec_cellscript::{generate_to_string, parse_schema_file, Options};
+
+#[test]
+fn shared_runtime_keeps_the_method_names() {
+    let ast = parse_schema_file("test/blockchain.mol".as_ref()).unwrap();
+    let mut options = Options::default();
+    options.runtime = Some("example.com/x/molecule".to_owned());
+    let code = generate_to_string(&ast, &options).unwrap();
+
+    assert!(code.contains("\t\"example.com/x/molecule\"\n"));
+    assert!(!code.contains("type Number"));
+    assert!(code.contains("func (s *RawHeader) Number() Uint64 {"));
+    assert!(code.contains("func (s *RawHeaderBuilder) Number(v Uint64) *RawHeaderBuilder {"));
+    assert!(code.contains("[4]molecule.Byte{"));
+    assert!(code.contains("molecule.HeaderSizeUint"));
+    assert!(code.contains("molecule.UnpackNumber(s.inner)"));
+    assert!(!code.contains(") molecule.Number("));
+}